@@ -0,0 +1,217 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Header, in dem Clients ihren API-Schlüssel mitschicken
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+// Pfade, die ohne Schlüssel und ohne Begrenzung erreichbar bleiben (Statusabfragen und Monitoring)
+pub const OPEN_PATHS: [&str; 5] = ["/", "/version", "/metrics", "/healthz", "/readyz"];
+
+/*
+   Endpunkte, an denen die Engine rechnet, nur sie werden begrenzt. Abfragen von Spielständen,
+   Aufzeichnungen und Stufen kosten kaum Rechenzeit und bleiben unbegrenzt
+    - POST /next_move, /next_move/stream: einzelne Züge
+    - POST /games, /games/{id}/moves: Züge der Engine in Spielen
+    - POST /review: Analyse einer ganzen Partie
+    - GET /games/{id}/watch, /matches/{id}/watch: Bewertungen für Zuschauer
+*/
+pub fn is_engine_route(method: &str, path: &str) -> bool {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    matches!(
+        (method, segments.as_slice()),
+        (
+            "POST",
+            ["next_move"] | ["next_move", "stream"] | ["games"] | ["review"]
+        ) | ("POST", ["games", _, "moves"])
+            | ("GET", ["games" | "matches", _, "watch"])
+    )
+}
+
+/*
+   Vergleicht zwei Geheimnisse in konstanter Zeit, damit die Antwortzeit nicht verrät, wie viele Zeichen
+   bereits stimmen. Nur die Länge ist ohne Vergleich der Inhalte erkennbar
+*/
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+// ab dieser Anzahl an Clients werden Buckets, die wieder voll sind, aus dem Speicher entfernt
+const MAX_IDLE_BUCKETS: usize = 10_000;
+
+#[derive(Debug, PartialEq)]
+pub enum AccessError {
+    MissingApiKey,
+    InvalidApiKey,
+    // Wartezeit, bis wieder eine Anfrage erlaubt ist
+    RateLimited(Duration),
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessError::MissingApiKey => write!(f, "missing api key in {API_KEY_HEADER} header"),
+            AccessError::InvalidApiKey => write!(f, "invalid api key"),
+            AccessError::RateLimited(retry_after) => write!(
+                f,
+                "too many requests, retry in {} ms",
+                retry_after.as_millis()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+/*
+   Erlaubte API-Schlüssel, einer pro Zeile. Leerzeilen und Zeilen mit # werden ignoriert,
+   damit Schlüssel in der Datei kommentiert werden können
+*/
+#[derive(Debug, Default)]
+pub struct ApiKeys {
+    keys: HashSet<String>,
+}
+
+impl ApiKeys {
+    pub fn load(path: &Path) -> Result<ApiKeys, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|error| format!("failed to read api keys {}: {error}", path.display()))?;
+        let keys = ApiKeys::parse(&content);
+        if keys.keys.is_empty() {
+            return Err(format!("api key file {} contains no keys", path.display()));
+        }
+        Ok(keys)
+    }
+
+    pub fn parse(content: &str) -> ApiKeys {
+        ApiKeys {
+            keys: content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from)
+                .collect(),
+        }
+    }
+
+    // vergleicht mit allen Schlüsseln, auch wenn ein früherer bereits passt
+    pub fn contains(&self, key: &str) -> bool {
+        self.keys
+            .iter()
+            .fold(false, |found, known| found | constant_time_eq(known, key))
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/*
+   Token-Bucket je Client: jeder Client darf burst Anfragen auf einmal stellen,
+   danach wird pro Minute um per_minute Anfragen aufgefüllt
+*/
+pub struct RateLimiter {
+    burst: f64,
+    per_second: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32, burst: u32) -> RateLimiter {
+        RateLimiter {
+            burst: burst.max(1) as f64,
+            per_second: per_minute as f64 / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // verbraucht ein Token des Clients, ohne Token wird die Wartezeit bis zum nächsten Token zurückgegeben
+    pub fn acquire(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_IDLE_BUCKETS {
+            buckets.retain(|_, bucket| self.tokens(bucket, now) < self.burst);
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = self.tokens(bucket, now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.per_second,
+            ))
+        }
+    }
+
+    fn tokens(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.per_second).min(self.burst)
+    }
+}
+
+/*
+   Zugangskontrolle für alle Endpunkte außer OPEN_PATHS
+    - api_keys: ohne Schlüssel ist der Server offen, sonst ist ein gültiger Schlüssel nötig
+    - rate_limiter: begrenzt die Anfragen an die Engine (is_engine_route) je Schlüssel bzw. ohne Schlüssel
+      je IP-Adresse
+*/
+#[derive(Default)]
+pub struct AccessControl {
+    pub api_keys: Option<ApiKeys>,
+    pub rate_limiter: Option<RateLimiter>,
+}
+
+impl AccessControl {
+    pub fn check(
+        &self,
+        method: &str,
+        path: &str,
+        api_key: Option<&str>,
+        ip: Option<&str>,
+    ) -> Result<(), AccessError> {
+        if OPEN_PATHS.contains(&path) {
+            return Ok(());
+        }
+
+        if let Some(api_keys) = &self.api_keys {
+            match api_key {
+                None => return Err(AccessError::MissingApiKey),
+                Some(api_key) if !api_keys.contains(api_key) => {
+                    return Err(AccessError::InvalidApiKey)
+                }
+                Some(_) => {}
+            }
+        }
+
+        if let Some(rate_limiter) = self
+            .rate_limiter
+            .as_ref()
+            .filter(|_| is_engine_route(method, path))
+        {
+            let client = match (api_key, ip) {
+                (Some(api_key), _) if self.api_keys.is_some() => format!("key:{api_key}"),
+                (_, Some(ip)) => format!("ip:{ip}"),
+                _ => "unknown".to_string(),
+            };
+            rate_limiter
+                .acquire(&client, Instant::now())
+                .map_err(AccessError::RateLimited)?;
+        }
+        Ok(())
+    }
+}
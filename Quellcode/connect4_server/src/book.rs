@@ -0,0 +1,123 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::connect4ai::{GameBoard, COMPUTER_PLAYER, USER_PLAYER, WIDTH};
+
+#[derive(Debug, PartialEq)]
+pub enum BookError {
+    Io(String),
+    // Zeilennummer (ab 1) und Beschreibung des Fehlers
+    Syntax(usize, String),
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookError::Io(error) => write!(f, "failed to read opening book: {error}"),
+            BookError::Syntax(line, error) => {
+                write!(f, "invalid opening book entry in line {line}: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+/*
+   Eröffnungsbuch mit vorgegebenen Antworten des Computers auf bekannte Stellungen.
+   Jede Zeile enthält die bisherigen Züge ab Spielbeginn und nach einem Doppelpunkt die möglichen
+   Antworten, jeweils als Spalten 0-6, z.B. "3 2 : 3 4". Leerzeilen und Zeilen mit # werden ignoriert.
+   Der Computer ist bei jeder Stellung am Zug, bei gerader Anzahl an Zügen hat er also begonnen
+*/
+#[derive(Debug, Default)]
+pub struct OpeningBook {
+    positions: HashMap<GameBoard, Vec<u8>>,
+}
+
+impl OpeningBook {
+    pub fn load(path: &Path) -> Result<OpeningBook, BookError> {
+        let content = fs::read_to_string(path)
+            .map_err(|error| BookError::Io(format!("{}: {error}", path.display())))?;
+        OpeningBook::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<OpeningBook, BookError> {
+        let mut positions = HashMap::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let syntax_error = |error: String| BookError::Syntax(index + 1, error);
+
+            let (moves, replies) = line
+                .split_once(':')
+                .ok_or_else(|| syntax_error("missing ':' between moves and replies".to_string()))?;
+            let moves = parse_columns(moves).map_err(syntax_error)?;
+            let replies = parse_columns(replies).map_err(syntax_error)?;
+            if replies.is_empty() {
+                return Err(syntax_error("no replies given".to_string()));
+            }
+
+            // wer begonnen hat, ergibt sich daraus, dass der Computer nach den Zügen am Zug ist
+            let mut player = if moves.len() % 2 == 0 {
+                COMPUTER_PLAYER
+            } else {
+                USER_PLAYER
+            };
+            let mut game_board = GameBoard::new();
+            for &column in &moves {
+                game_board
+                    .drop_chip(column as usize, player)
+                    .ok_or_else(|| syntax_error(format!("column {column} is full")))?;
+                player = if player == COMPUTER_PLAYER {
+                    USER_PLAYER
+                } else {
+                    COMPUTER_PLAYER
+                };
+            }
+            for &column in &replies {
+                if game_board
+                    .clone()
+                    .drop_chip(column as usize, player)
+                    .is_none()
+                {
+                    return Err(syntax_error(format!("reply column {column} is full")));
+                }
+            }
+
+            positions.insert(game_board, replies);
+        }
+        Ok(OpeningBook { positions })
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    // wählt zufällig eine der Antworten auf die Stellung, None wenn die Stellung nicht im Buch steht
+    pub fn choose<R: Rng>(&self, game_board: &GameBoard, rng: &mut R) -> Option<u8> {
+        self.positions.get(game_board)?.choose(rng).copied()
+    }
+}
+
+fn parse_columns(columns: &str) -> Result<Vec<u8>, String> {
+    columns
+        .split_whitespace()
+        .map(|column| match column.parse::<u8>() {
+            Ok(column) if (column as usize) < WIDTH => Ok(column),
+            _ => Err(format!("invalid column {column}")),
+        })
+        .collect()
+}
@@ -0,0 +1,157 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/*
+   Signal zum Abbrechen einer laufenden Suche. Alle Kopien eines Tokens teilen denselben Zustand,
+   ein abgebrochenes Token bleibt abgebrochen
+*/
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn same_token(&self, other: &CancelToken) -> bool {
+        Arc::ptr_eq(&self.cancelled, &other.cancelled)
+    }
+}
+
+/*
+   laufende Suchen unter einer vom Aufrufer gewählten Id, damit sie von außen abgebrochen werden können.
+   Suchen ohne Id werden nur mitgezählt, damit sie beim Herunterfahren gemeinsam abgebrochen werden können
+*/
+#[derive(Debug, Default)]
+pub struct SearchRegistry {
+    searches: Mutex<HashMap<String, CancelToken>>,
+    anonymous: Mutex<Vec<CancelToken>>,
+}
+
+impl SearchRegistry {
+    pub fn new() -> SearchRegistry {
+        SearchRegistry::default()
+    }
+
+    // eine ältere Suche mit derselben Id wird abgebrochen und ersetzt
+    pub fn register(&self, id: &str, token: CancelToken) {
+        if let Some(previous) = self.searches.lock().unwrap().insert(id.to_string(), token) {
+            previous.cancel();
+        }
+    }
+
+    // entfernt die Suche, sofern die Id nicht bereits von einer neueren Suche verwendet wird
+    pub fn unregister(&self, id: &str, token: &CancelToken) {
+        let mut searches = self.searches.lock().unwrap();
+        if searches
+            .get(id)
+            .is_some_and(|current| current.same_token(token))
+        {
+            searches.remove(id);
+        }
+    }
+
+    // bricht die Suche mit der Id ab, false wenn keine solche Suche läuft
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.searches.lock().unwrap().remove(id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn track(&self, token: CancelToken) {
+        self.anonymous.lock().unwrap().push(token);
+    }
+
+    pub fn untrack(&self, token: &CancelToken) {
+        self.anonymous
+            .lock()
+            .unwrap()
+            .retain(|tracked| !tracked.same_token(token));
+    }
+
+    // bricht alle Suchen ab, auch die ohne Id
+    pub fn cancel_all(&self) {
+        for (_, token) in self.searches.lock().unwrap().drain() {
+            token.cancel();
+        }
+        for token in self.anonymous.lock().unwrap().drain(..) {
+            token.cancel();
+        }
+    }
+
+    // Anzahl aller laufenden Suchen mit und ohne Id
+    pub fn in_flight(&self) -> usize {
+        self.len() + self.anonymous.lock().unwrap().len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.searches.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/*
+   Bricht die Suche ab, sobald der Guard fallen gelassen wird, z.B. wenn actix die Anfrage
+   nach einem Verbindungsabbruch des Clients verwirft. Eine registrierte Suche wird dabei ausgetragen
+*/
+pub struct CancelGuard {
+    token: CancelToken,
+    // Registry und Id der Suche, ohne Id ist die Suche nur mitgezählt
+    registration: Option<(Arc<SearchRegistry>, Option<String>)>,
+}
+
+impl CancelGuard {
+    pub fn new(token: CancelToken) -> CancelGuard {
+        CancelGuard {
+            token,
+            registration: None,
+        }
+    }
+
+    pub fn registered(token: CancelToken, registry: Arc<SearchRegistry>, id: &str) -> CancelGuard {
+        registry.register(id, token.clone());
+        CancelGuard {
+            token,
+            registration: Some((registry, Some(id.to_string()))),
+        }
+    }
+
+    pub fn tracked(token: CancelToken, registry: Arc<SearchRegistry>) -> CancelGuard {
+        registry.track(token.clone());
+        CancelGuard {
+            token,
+            registration: Some((registry, None)),
+        }
+    }
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        self.token.cancel();
+        match &self.registration {
+            Some((registry, Some(id))) => registry.unregister(id, &self.token),
+            Some((registry, None)) => registry.untrack(&self.token),
+            None => {}
+        }
+    }
+}
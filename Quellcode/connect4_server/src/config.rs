@@ -0,0 +1,587 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use clap::{Parser, ValueEnum};
+use serde::Deserialize;
+
+use crate::access::{AccessControl, ApiKeys, RateLimiter};
+use crate::book::{BookError, OpeningBook};
+use crate::connect4ai::SearchOptions;
+use crate::cors::CorsPolicy;
+use crate::history::{GameHistory, HistoryError};
+use crate::ladder::{get_level, TakebackLimits, HARD_LEVEL};
+use crate::logging::LogFormat;
+#[cfg(feature = "tls")]
+use crate::tls::CertificateStore;
+use crate::transposition::entries_for_size_mb;
+
+// die Oberfläche läuft auf demselben Rechner, von außen ist der Server nur nach expliziter Konfiguration erreichbar
+pub const DEFAULT_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+pub const DEFAULT_PORT: u16 = 51338;
+pub const DEFAULT_LOG_LEVEL: &str = "actix_server=info,connect4_server=info";
+pub const DEFAULT_TT_SIZE_MB: usize = 1;
+pub const DEFAULT_QUEUE_LIMIT: usize = 64;
+// ohne Angabe bekommt jede Suche so viele Kerne, nur auf größeren Rechnern laufen mehrere Suchen gleichzeitig
+pub const DEFAULT_THREADS_PER_SEARCH: usize = 4;
+pub const DEFAULT_SEARCH_TIMEOUT_MS: u64 = 10_000;
+// eine Nachbesprechung sucht für jede Stellung des Spiels und braucht deshalb länger als ein einzelner Zug
+pub const DEFAULT_REVIEW_TIMEOUT_MS: u64 = 30_000;
+pub const DEFAULT_SESSION_TIMEOUT_S: u64 = 30 * 60;
+pub const DEFAULT_SHUTDOWN_TIMEOUT_S: u64 = 10;
+pub const DEFAULT_RATE_LIMIT: u32 = 120;
+pub const DEFAULT_RATE_LIMIT_BURST: u32 = 20;
+
+// größte erlaubte Transpositionstabelle je Suche
+const MAX_TT_SIZE_MB: usize = 4096;
+const LOG_LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+
+/*
+   Kommandozeilenparameter, jeder kann auch über eine Umgebungsvariable gesetzt werden.
+   Nicht gesetzte Werte werden aus der Konfigurationsdatei bzw. den Standardwerten übernommen,
+   deshalb sind alle Parameter optional
+*/
+#[derive(Debug, Default, Parser)]
+#[command(
+    name = "connect4_server",
+    version,
+    about = "Connect 4 engine server for the Vier Gewinnt UI"
+)]
+pub struct Cli {
+    #[arg(
+        long,
+        env = "CONNECT4_CONFIG",
+        help = "TOML file with the same settings as the flags below (snake_case keys)"
+    )]
+    pub config: Option<PathBuf>,
+    #[arg(
+        long,
+        env = "CONNECT4_ADDRESS",
+        help = "Address to bind to [default: 127.0.0.1]"
+    )]
+    pub address: Option<IpAddr>,
+    #[arg(
+        long,
+        env = "CONNECT4_PORT",
+        help = "Port to listen on [default: 51338]"
+    )]
+    pub port: Option<u16>,
+    #[arg(
+        long,
+        env = "CONNECT4_WORKERS",
+        help = "Number of HTTP worker threads [default: number of cores]"
+    )]
+    pub workers: Option<usize>,
+    #[arg(
+        long,
+        env = "RUST_LOG",
+        help = "Log filter in env_logger syntax [default: actix_server=info,connect4_server=info]"
+    )]
+    pub log_level: Option<String>,
+    #[arg(
+        long,
+        env = "CONNECT4_LOG_FORMAT",
+        help = "Log output as plain text or as one JSON object per line [default: text]"
+    )]
+    pub log_format: Option<LogFormat>,
+    #[arg(
+        long,
+        env = "CONNECT4_DEFAULT_LEVEL",
+        help = "Level used by /next_move when neither level nor difficulty is given [default: 9]"
+    )]
+    pub default_level: Option<u8>,
+    #[arg(
+        long,
+        env = "CONNECT4_OPENING_BOOK",
+        help = "Opening book file, positions in the book are answered without search on levels without mistakes"
+    )]
+    pub opening_book: Option<PathBuf>,
+    #[arg(
+        long,
+        env = "CONNECT4_TT_SIZE_MB",
+        help = "Size of the transposition table of each search in MiB, pondering games share at most one table per core [default: 1]"
+    )]
+    pub tt_size_mb: Option<usize>,
+    #[arg(
+        long,
+        env = "CONNECT4_SEARCH_THREADS",
+        help = "Threads used by a single search [default: number of cores / compute workers]"
+    )]
+    pub search_threads: Option<usize>,
+    #[arg(
+        long,
+        env = "CONNECT4_COMPUTE_WORKERS",
+        help = "Searches running at the same time [default: number of cores / 4, at least 1]"
+    )]
+    pub compute_workers: Option<usize>,
+    #[arg(
+        long,
+        env = "CONNECT4_QUEUE_LIMIT",
+        help = "Searches waiting for a compute worker before requests are rejected [default: 64]"
+    )]
+    pub queue_limit: Option<usize>,
+    #[arg(
+        long,
+        env = "CONNECT4_SEARCH_TIMEOUT_MS",
+        help = "Time after which a search plays its best move so far [default: 10000]"
+    )]
+    pub search_timeout_ms: Option<u64>,
+    #[arg(
+        long,
+        env = "CONNECT4_REVIEW_TIMEOUT_MS",
+        help = "Time after which a game review returns the moves reviewed so far [default: 30000]"
+    )]
+    pub review_timeout_ms: Option<u64>,
+    #[arg(
+        long,
+        env = "CONNECT4_SESSION_TIMEOUT_S",
+        help = "Idle time after which session games are removed [default: 1800]"
+    )]
+    pub session_timeout_s: Option<u64>,
+    #[arg(
+        long,
+        env = "CONNECT4_SHUTDOWN_TIMEOUT_S",
+        help = "Time running searches may take to finish on shutdown before they play their best move so far [default: 10]"
+    )]
+    pub shutdown_timeout_s: Option<u64>,
+    #[arg(
+        long,
+        env = "CONNECT4_SESSION_SNAPSHOT",
+        help = "File in which session games are saved on shutdown and restored from on startup"
+    )]
+    pub session_snapshot: Option<PathBuf>,
+    #[arg(
+        long,
+        env = "CONNECT4_HISTORY_DB",
+        help = "SQLite database in which finished session games and matches are recorded, see GET /history"
+    )]
+    pub history_db: Option<PathBuf>,
+    #[arg(
+        long,
+        env = "CONNECT4_TAKEBACKS",
+        value_delimiter = ',',
+        help = "Takebacks allowed per session game as LEVEL=COUNT, repeatable [default: 5 on level 1 down to 0 on level 9]"
+    )]
+    pub takebacks: Option<Vec<String>>,
+    #[arg(
+        long = "cors-origin",
+        env = "CONNECT4_CORS_ORIGINS",
+        value_delimiter = ',',
+        help = "Origin allowed to call the server from a browser, repeatable [default: file://]"
+    )]
+    pub cors_origins: Option<Vec<String>>,
+    #[arg(
+        long = "cors-method",
+        env = "CONNECT4_CORS_METHODS",
+        value_delimiter = ',',
+        help = "Method allowed for cross-origin requests, repeatable [default: GET,POST,DELETE]"
+    )]
+    pub cors_methods: Option<Vec<String>>,
+    #[arg(
+        long = "cors-header",
+        env = "CONNECT4_CORS_HEADERS",
+        value_delimiter = ',',
+        help = "Header allowed for cross-origin requests, repeatable [default: Content-Type,X-Api-Key]"
+    )]
+    pub cors_headers: Option<Vec<String>>,
+    #[arg(
+        long,
+        env = "CONNECT4_CORS_PERMISSIVE",
+        help = "Allow any origin, method and header (development only)"
+    )]
+    pub cors_permissive: bool,
+    #[arg(
+        long,
+        env = "CONNECT4_API_KEYS_FILE",
+        help = "File with one allowed api key per line, clients send it in the X-Api-Key header or, for WebSockets, as ?api_key="
+    )]
+    pub api_keys_file: Option<PathBuf>,
+    #[arg(
+        long,
+        env = "CONNECT4_RATE_LIMIT",
+        help = "Engine requests per minute allowed per api key or IP address, 0 disables the limit [default: 120]"
+    )]
+    pub rate_limit: Option<u32>,
+    #[arg(
+        long,
+        env = "CONNECT4_RATE_LIMIT_BURST",
+        help = "Engine requests a client may send at once before the rate limit applies [default: 20]"
+    )]
+    pub rate_limit_burst: Option<u32>,
+    #[arg(
+        long,
+        env = "CONNECT4_TLS_CERT",
+        help = "PEM certificate chain, serves HTTPS instead of HTTP (reloaded on SIGHUP)"
+    )]
+    pub tls_cert: Option<PathBuf>,
+    #[arg(
+        long,
+        env = "CONNECT4_TLS_KEY",
+        help = "PEM private key of the certificate"
+    )]
+    pub tls_key: Option<PathBuf>,
+    #[arg(
+        long,
+        env = "CONNECT4_HTTP_PORT",
+        help = "Additional plain HTTP port when serving HTTPS, see --plain-http"
+    )]
+    pub http_port: Option<u16>,
+    #[arg(
+        long,
+        env = "CONNECT4_PLAIN_HTTP",
+        help = "Answer on the plain HTTP port with a redirect to HTTPS or refuse the request [default: redirect]"
+    )]
+    pub plain_http: Option<PlainHttp>,
+}
+
+// Verhalten auf dem zusätzlichen HTTP-Port, wenn der Server HTTPS anbietet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PlainHttp {
+    // leitet auf dieselbe Adresse per HTTPS weiter
+    #[default]
+    Redirect,
+    // lehnt die Anfrage mit einem Hinweis auf HTTPS ab
+    Refuse,
+}
+
+// Inhalt der Konfigurationsdatei, unbekannte Schlüssel werden als Tippfehler abgelehnt
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    pub address: Option<IpAddr>,
+    pub port: Option<u16>,
+    pub workers: Option<usize>,
+    pub log_level: Option<String>,
+    pub log_format: Option<LogFormat>,
+    pub default_level: Option<u8>,
+    pub opening_book: Option<PathBuf>,
+    pub tt_size_mb: Option<usize>,
+    pub search_threads: Option<usize>,
+    pub compute_workers: Option<usize>,
+    pub queue_limit: Option<usize>,
+    pub search_timeout_ms: Option<u64>,
+    pub review_timeout_ms: Option<u64>,
+    pub session_timeout_s: Option<u64>,
+    pub shutdown_timeout_s: Option<u64>,
+    pub session_snapshot: Option<PathBuf>,
+    pub history_db: Option<PathBuf>,
+    pub takebacks: Option<Vec<String>>,
+    pub cors_origins: Option<Vec<String>>,
+    pub cors_methods: Option<Vec<String>>,
+    pub cors_headers: Option<Vec<String>>,
+    pub cors_permissive: Option<bool>,
+    pub api_keys_file: Option<PathBuf>,
+    pub rate_limit: Option<u32>,
+    pub rate_limit_burst: Option<u32>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub http_port: Option<u16>,
+    pub plain_http: Option<PlainHttp>,
+}
+
+impl FileConfig {
+    pub fn load(path: &Path) -> Result<FileConfig, ConfigError> {
+        let content = fs::read_to_string(path)
+            .map_err(|error| ConfigError::File(path.to_path_buf(), error.to_string()))?;
+        toml::from_str(&content)
+            .map_err(|error| ConfigError::File(path.to_path_buf(), error.to_string()))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    File(PathBuf, String),
+    Invalid(String),
+    Book(BookError),
+    History(HistoryError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::File(path, error) => {
+                write!(f, "invalid config file {}: {error}", path.display())
+            }
+            ConfigError::Invalid(error) => write!(f, "invalid configuration: {error}"),
+            ConfigError::Book(error) => error.fmt(f),
+            ConfigError::History(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/*
+   Vollständige Einstellungen des Servers.
+   Vorrang: Kommandozeile, dann Umgebungsvariablen, dann Konfigurationsdatei, dann Standardwerte
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub address: IpAddr,
+    pub port: u16,
+    pub workers: usize,
+    pub log_level: String,
+    pub log_format: LogFormat,
+    pub default_level: u8,
+    pub opening_book: Option<PathBuf>,
+    pub tt_size_mb: usize,
+    pub search_threads: usize,
+    pub compute_workers: usize,
+    pub queue_limit: usize,
+    pub search_timeout: Duration,
+    pub review_timeout: Duration,
+    pub session_timeout: Duration,
+    pub shutdown_timeout: Duration,
+    pub session_snapshot: Option<PathBuf>,
+    pub history_db: Option<PathBuf>,
+    pub takebacks: TakebackLimits,
+    pub cors: CorsPolicy,
+    pub api_keys_file: Option<PathBuf>,
+    pub rate_limit: u32,
+    pub rate_limit_burst: u32,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub http_port: Option<u16>,
+    pub plain_http: PlainHttp,
+}
+
+impl Config {
+    // liest Kommandozeile, Umgebung und Konfigurationsdatei, bei --help und falschen Parametern endet das Programm
+    pub fn load() -> Result<Config, ConfigError> {
+        let cli = Cli::parse();
+        let file = match &cli.config {
+            Some(path) => FileConfig::load(path)?,
+            None => FileConfig::default(),
+        };
+        Config::resolve(cli, file)
+    }
+
+    pub fn resolve(cli: Cli, file: FileConfig) -> Result<Config, ConfigError> {
+        let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+        Config::resolve_for_cores(cli, file, cores)
+    }
+
+    // wie resolve, die Standardwerte der Threads richten sich nach der übergebenen Anzahl an Kernen
+    pub fn resolve_for_cores(
+        cli: Cli,
+        file: FileConfig,
+        cores: usize,
+    ) -> Result<Config, ConfigError> {
+        let takebacks =
+            TakebackLimits::parse(&cli.takebacks.or(file.takebacks).unwrap_or_default())
+                .map_err(ConfigError::Invalid)?;
+        let compute_workers = cli
+            .compute_workers
+            .or(file.compute_workers)
+            .unwrap_or((cores / DEFAULT_THREADS_PER_SEARCH).max(1));
+        let config = Config {
+            address: cli.address.or(file.address).unwrap_or(DEFAULT_ADDRESS),
+            port: cli.port.or(file.port).unwrap_or(DEFAULT_PORT),
+            workers: cli.workers.or(file.workers).unwrap_or(cores),
+            log_level: cli
+                .log_level
+                .or(file.log_level)
+                .unwrap_or_else(|| DEFAULT_LOG_LEVEL.to_string()),
+            log_format: cli.log_format.or(file.log_format).unwrap_or_default(),
+            default_level: cli
+                .default_level
+                .or(file.default_level)
+                .unwrap_or(HARD_LEVEL),
+            opening_book: cli.opening_book.or(file.opening_book),
+            tt_size_mb: cli
+                .tt_size_mb
+                .or(file.tt_size_mb)
+                .unwrap_or(DEFAULT_TT_SIZE_MB),
+            // die parallel laufenden Suchen teilen sich die Kerne, statt sie mehrfach zu belegen
+            search_threads: cli
+                .search_threads
+                .or(file.search_threads)
+                .unwrap_or((cores / compute_workers.max(1)).max(1)),
+            compute_workers,
+            queue_limit: cli
+                .queue_limit
+                .or(file.queue_limit)
+                .unwrap_or(DEFAULT_QUEUE_LIMIT),
+            search_timeout: Duration::from_millis(
+                cli.search_timeout_ms
+                    .or(file.search_timeout_ms)
+                    .unwrap_or(DEFAULT_SEARCH_TIMEOUT_MS),
+            ),
+            review_timeout: Duration::from_millis(
+                cli.review_timeout_ms
+                    .or(file.review_timeout_ms)
+                    .unwrap_or(DEFAULT_REVIEW_TIMEOUT_MS),
+            ),
+            session_timeout: Duration::from_secs(
+                cli.session_timeout_s
+                    .or(file.session_timeout_s)
+                    .unwrap_or(DEFAULT_SESSION_TIMEOUT_S),
+            ),
+            shutdown_timeout: Duration::from_secs(
+                cli.shutdown_timeout_s
+                    .or(file.shutdown_timeout_s)
+                    .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_S),
+            ),
+            session_snapshot: cli.session_snapshot.or(file.session_snapshot),
+            history_db: cli.history_db.or(file.history_db),
+            takebacks,
+            cors: CorsPolicy {
+                permissive: cli.cors_permissive || file.cors_permissive.unwrap_or_default(),
+                origins: cli
+                    .cors_origins
+                    .or(file.cors_origins)
+                    .unwrap_or(CorsPolicy::default().origins),
+                methods: cli
+                    .cors_methods
+                    .or(file.cors_methods)
+                    .unwrap_or(CorsPolicy::default().methods),
+                headers: cli
+                    .cors_headers
+                    .or(file.cors_headers)
+                    .unwrap_or(CorsPolicy::default().headers),
+            },
+            api_keys_file: cli.api_keys_file.or(file.api_keys_file),
+            rate_limit: cli
+                .rate_limit
+                .or(file.rate_limit)
+                .unwrap_or(DEFAULT_RATE_LIMIT),
+            rate_limit_burst: cli
+                .rate_limit_burst
+                .or(file.rate_limit_burst)
+                .unwrap_or(DEFAULT_RATE_LIMIT_BURST),
+            tls_cert: cli.tls_cert.or(file.tls_cert),
+            tls_key: cli.tls_key.or(file.tls_key),
+            http_port: cli.http_port.or(file.http_port),
+            plain_http: cli.plain_http.or(file.plain_http).unwrap_or_default(),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |error: String| Err(ConfigError::Invalid(error));
+        if self.port == 0 {
+            return invalid("port must not be 0".to_string());
+        }
+        for (name, value) in [
+            ("workers", self.workers),
+            ("search_threads", self.search_threads),
+            ("compute_workers", self.compute_workers),
+        ] {
+            if value == 0 {
+                return invalid(format!("{name} must be at least 1"));
+            }
+        }
+        if !(1..=MAX_TT_SIZE_MB).contains(&self.tt_size_mb) {
+            return invalid(format!("tt_size_mb must be between 1 and {MAX_TT_SIZE_MB}"));
+        }
+        if self.search_timeout.is_zero()
+            || self.review_timeout.is_zero()
+            || self.session_timeout.is_zero()
+        {
+            return invalid("timeouts must be greater than 0".to_string());
+        }
+        if let Err(error) = get_level(self.default_level) {
+            return invalid(format!("default_level: {error}"));
+        }
+        if self.rate_limit > 0 && self.rate_limit_burst == 0 {
+            return invalid("rate_limit_burst must be at least 1".to_string());
+        }
+        if self.tls_cert.is_some() != self.tls_key.is_some() {
+            return invalid("tls_cert and tls_key must be given together".to_string());
+        }
+        if self.tls_cert.is_some() && !cfg!(feature = "tls") {
+            return invalid("tls requires a server built with the tls feature".to_string());
+        }
+        match self.http_port {
+            Some(_) if self.tls_cert.is_none() => {
+                return invalid("http_port requires tls_cert and tls_key".to_string())
+            }
+            Some(http_port) if http_port == 0 || http_port == self.port => {
+                return invalid("http_port must differ from port and not be 0".to_string())
+            }
+            _ => {}
+        }
+        self.cors.validate().map_err(ConfigError::Invalid)?;
+        validate_log_level(&self.log_level).map_err(ConfigError::Invalid)
+    }
+
+    // Sucheinstellungen für alle Züge der Engine, lädt dafür auch das Eröffnungsbuch
+    pub fn search_options(&self) -> Result<SearchOptions, ConfigError> {
+        let opening_book = match &self.opening_book {
+            Some(path) => Some(Arc::new(
+                OpeningBook::load(path).map_err(ConfigError::Book)?,
+            )),
+            None => None,
+        };
+        Ok(SearchOptions {
+            threads: self.search_threads,
+            transposition_table_entries: entries_for_size_mb(self.tt_size_mb),
+            opening_book,
+            ..SearchOptions::default()
+        })
+    }
+
+    // Zertifikat für HTTPS, None wenn der Server unverschlüsselt antwortet
+    #[cfg(feature = "tls")]
+    pub fn certificate_store(&self) -> Result<Option<CertificateStore>, ConfigError> {
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(certificate), Some(key)) => CertificateStore::load(certificate, key)
+                .map(Some)
+                .map_err(|error| ConfigError::Invalid(error.to_string())),
+            _ => Ok(None),
+        }
+    }
+
+    // Zugangskontrolle aller Endpunkte, lädt dafür auch die API-Schlüssel
+    pub fn access_control(&self) -> Result<AccessControl, ConfigError> {
+        let api_keys = match &self.api_keys_file {
+            Some(path) => Some(ApiKeys::load(path).map_err(ConfigError::Invalid)?),
+            None => None,
+        };
+        Ok(AccessControl {
+            api_keys,
+            rate_limiter: (self.rate_limit > 0)
+                .then(|| RateLimiter::new(self.rate_limit, self.rate_limit_burst)),
+        })
+    }
+
+    // öffnet den Verlauf der Spiele und legt dabei fehlende Tabellen an
+    pub fn game_history(&self) -> Result<Option<GameHistory>, ConfigError> {
+        match &self.history_db {
+            Some(path) => GameHistory::open(path)
+                .map(Some)
+                .map_err(ConfigError::History),
+            None => Ok(None),
+        }
+    }
+}
+
+/*
+   Prüft einen Filter in der Syntax von env_logger (z.B. "info" oder "actix_web=debug,connect4_server=trace"),
+   da env_logger ungültige Stufen sonst stillschweigend ignoriert
+*/
+fn validate_log_level(log_level: &str) -> Result<(), String> {
+    let filter = log_level.split('/').next().unwrap_or_default();
+    for directive in filter.split(',').map(str::trim) {
+        if directive.is_empty() {
+            continue;
+        }
+        if let Some((module, level)) = directive.split_once('=') {
+            if module.is_empty() || !LOG_LEVELS.contains(&level.to_lowercase().as_str()) {
+                return Err(format!(
+                    "invalid log level directive '{directive}', expected one of {}",
+                    LOG_LEVELS.join(", ")
+                ));
+            }
+        }
+    }
+    Ok(())
+}
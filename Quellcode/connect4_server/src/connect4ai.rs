@@ -1,51 +1,68 @@
 #![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
 
 use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 
+use crate::book::OpeningBook;
+use crate::cancellation::CancelToken;
+use crate::ladder::{get_level, level_from_difficulty, DifficultyError};
+use crate::skill::Skill;
+use crate::transposition::{position_key, Bound, TranspositionTable, TtEntry, DEFAULT_ENTRIES};
+
 pub const USER_PLAYER: u8 = 1;
 pub const COMPUTER_PLAYER: u8 = 2;
 pub const HEIGHT: usize = 6;
 pub const WIDTH: usize = 7;
 
-pub const MAX_SCORE: i64 = i64::MAX;
+// Sieg-Scores werden als "Sieg in N Halbzügen" kodiert: MAX_SCORE - N (bzw. -(MAX_SCORE - N) bei Niederlage)
+pub const MAX_SCORE: i64 = 1_000_000_000_000;
 const MIN_SCORE: i64 = -MAX_SCORE;
-const ZUGZWANG_SCORE: i64 = 100000000;
-const THREAT_L4_SCORE: i64 = 10000;
+const MAX_PLIES: i64 = (WIDTH * HEIGHT) as i64;
+pub const ZUGZWANG_SCORE: i64 = 100000000;
+pub const THREAT_L4_SCORE: i64 = 10000;
 const CENTRALITY_SCORE: usize = 1000;
 
+#[derive(Debug, Clone)]
 pub struct Difficulty {
-    calculation_depth: u8,
-    zugzwang_evaluation: bool,
+    // Stufe der Schwierigkeitsleiter, None bei eigens zusammengestellten Schwierigkeiten
+    pub(crate) level: Option<u8>,
+    // Tiefe, die immer vollständig durchsucht wird; mit time_limit wird danach tiefer gesucht, solange Zeit bleibt
+    pub(crate) calculation_depth: u8,
+    pub(crate) time_limit: Option<Duration>,
+    pub(crate) zugzwang_evaluation: bool,
+    pub(crate) skill: Skill,
 }
 
 impl Difficulty {
+    // bisherige Schwierigkeiten: 0 -> Easy, 1 -> Medium, alles andere -> Hard, wie früher mit fester Tiefe
     pub fn from_int(difficulty: u8) -> Difficulty {
-        match difficulty {
-            // Easy
-            0 => Difficulty {
-                calculation_depth: 4,
-                zugzwang_evaluation: false,
-            },
+        let level = level_from_difficulty(difficulty.min(2)).unwrap();
+        get_level(level).unwrap().difficulty().fixed_depth()
+    }
 
-            // Medium
-            1 => Difficulty {
-                calculation_depth: 6,
-                zugzwang_evaluation: true,
-            },
+    // Stufe der Schwierigkeitsleiter, unbekannte Stufen werden abgelehnt
+    pub fn from_level(level: u8) -> Result<Difficulty, DifficultyError> {
+        Ok(get_level(level)?.difficulty())
+    }
 
-            // Hard
-            _ => Difficulty {
-                calculation_depth: 8,
-                zugzwang_evaluation: true,
-            },
+    // dieselbe Schwierigkeit ohne Bedenkzeit: die Suche endet immer nach calculation_depth, unabhängig von der Hardware
+    pub fn fixed_depth(&self) -> Difficulty {
+        Difficulty {
+            time_limit: None,
+            ..self.clone()
         }
     }
 }
 
-#[derive(PartialEq, Debug, Serialize)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum NextMoveResult {
     NextMove,
     ComputerWins,
@@ -66,7 +83,7 @@ impl Field {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, Hash, PartialEq)]
 pub struct GameBoard {
     pub(crate) grid: [[u8; WIDTH]; HEIGHT],
 }
@@ -87,6 +104,19 @@ impl GameBoard {
         self.grid[y][x] = value;
     }
 
+    // Stellung als Text für Logs: Zeilen von oben nach unten, getrennt durch '/', Felder wie im JSON-Brett (0, 1, 2)
+    pub fn position(&self) -> String {
+        self.grid
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|field| field.to_string())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
     fn height(&self) -> usize {
         WIDTH
     }
@@ -94,6 +124,35 @@ impl GameBoard {
     pub fn from(grid: [[u8; WIDTH]; HEIGHT]) -> GameBoard {
         GameBoard { grid }
     }
+
+    // lässt einen Stein in die übergebene Spalte fallen, gibt None zurück wenn die Spalte voll ist
+    pub fn drop_chip(&mut self, x: usize, player: u8) -> Option<Field> {
+        let field = available_fields(self)
+            .into_iter()
+            .find(|field| field.x as usize == x)?;
+        self.set(field.x as usize, field.y as usize, player);
+        Some(field)
+    }
+
+    // nimmt den obersten Stein der Spalte wieder heraus, gibt None zurück wenn die Spalte leer ist
+    pub fn take_chip(&mut self, x: usize) -> Option<Field> {
+        let y = (0..HEIGHT).find(|&y| self.get(x, y) != 0)?;
+        self.set(x, y, 0);
+        Some(Field::new(x as u8, y as u8))
+    }
+
+    // tauscht die Steine beider Spieler, damit die Engine auch für den Nutzer ziehen kann
+    pub fn swap_players(&self) -> GameBoard {
+        let mut swapped = self.clone();
+        for row in swapped.grid.iter_mut() {
+            for field in row.iter_mut() {
+                if *field != 0 {
+                    *field = other_player(*field);
+                }
+            }
+        }
+        swapped
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -115,42 +174,366 @@ impl Zugzwang {
     pub fn create(fulfilment_position: Field, player: u8) -> Zugzwang {
         Zugzwang {
             fulfilment_position,
-            even: fulfilment_position.y % 2 == 0,
+            even: fulfilment_position.y.is_multiple_of(2),
             player,
         }
     }
 }
 
+// Zufallsgenerator für eine Suche: mit Seed reproduzierbar, ohne Seed zufällig initialisiert
+pub fn rng_from_seed(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/*
+   Einstellungen der Suche, die nicht von der Schwierigkeit abhängen
+    - threads: Anzahl der Threads, auf die die Züge an der Wurzel verteilt werden (1: deterministisch)
+    - transposition_table: geteilte Tabelle, ohne wird für jede Suche eine neue Tabelle angelegt
+    - cancel: bricht die Suche ab, es wird der beste Zug der letzten vollständigen Iteration gespielt
+    - progress: wird nach jeder vollständigen Iteration mit deren Ergebnis aufgerufen
+    - transposition_table_entries: Größe der Tabellen, die für einzelne Suchen angelegt werden
+    - opening_book: steht die Stellung im Buch, wird ohne Suche eine der Antworten des Buchs gespielt (nur bei
+      fehlerfreiem Spiel, schwächere Stufen würden sonst in der Eröffnung perfekt spielen)
+    - report: wird nach jeder Suche mit deren Zusammenfassung aufgerufen (Metriken, Logs)
+*/
+#[derive(Clone)]
+pub struct SearchOptions {
+    pub threads: usize,
+    pub transposition_table: Option<Arc<TranspositionTable>>,
+    pub cancel: CancelToken,
+    pub progress: Option<ProgressCallback>,
+    pub transposition_table_entries: usize,
+    pub opening_book: Option<Arc<OpeningBook>>,
+    pub report: Option<ReportCallback>,
+    // Id der HTTP-Anfrage bzw. des Spiels, werden unverändert in den SearchReport übernommen
+    pub request_id: Option<String>,
+    pub game_id: Option<String>,
+}
+
+pub type ProgressCallback = Arc<dyn Fn(&SearchInfo) + Send + Sync>;
+pub type ReportCallback = Arc<dyn Fn(&SearchReport) + Send + Sync>;
+
+/*
+   Zwischenstand der Suche nach einer Iteration
+    - best_column: Spalte des bisher besten Zugs
+    - nodes: Anzahl der bisher durchsuchten Stellungen
+    - pv: Hauptvariante, die erwarteten Züge beider Seiten beginnend mit dem besten Zug
+*/
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchInfo {
+    pub depth: u8,
+    pub best_column: Option<u8>,
+    pub score: i64,
+    pub nodes: u64,
+    pub pv: Vec<u8>,
+    pub elapsed_ms: u64,
+}
+
+/*
+   Zusammenfassung eines Zugs der Engine
+    - position: Stellung vor dem Zug, siehe GameBoard::position
+    - level: Stufe der Schwierigkeit, None bei eigens zusammengestellten Schwierigkeiten
+    - depth: Tiefe der letzten vollständigen Iteration, 0 bei Zügen aus dem Eröffnungsbuch
+    - nodes und Cache-Statistiken: Summe über alle Threads der Suche
+*/
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchReport {
+    pub request_id: Option<String>,
+    pub game_id: Option<String>,
+    pub position: String,
+    pub level: Option<u8>,
+    pub column: Option<u8>,
+    pub score: i64,
+    pub result: NextMoveResult,
+    pub blunder: bool,
+    pub book: bool,
+    pub cancelled: bool,
+    pub depth: u8,
+    pub nodes: u64,
+    pub elapsed_ms: u64,
+    pub evaluation_cache_hits: u64,
+    pub evaluation_cache_misses: u64,
+    pub tt_probes: u64,
+    pub tt_hits: u64,
+}
+
+impl SearchReport {
+    fn new(level: Option<u8>, position: String, options: &SearchOptions) -> SearchReport {
+        SearchReport {
+            request_id: options.request_id.clone(),
+            game_id: options.game_id.clone(),
+            position,
+            level,
+            column: None,
+            score: 0,
+            result: NextMoveResult::None,
+            blunder: false,
+            book: false,
+            cancelled: false,
+            depth: 0,
+            nodes: 0,
+            elapsed_ms: 0,
+            evaluation_cache_hits: 0,
+            evaluation_cache_misses: 0,
+            tt_probes: 0,
+            tt_hits: 0,
+        }
+    }
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            threads: 1,
+            transposition_table: None,
+            cancel: CancelToken::new(),
+            progress: None,
+            transposition_table_entries: DEFAULT_ENTRIES,
+            opening_book: None,
+            report: None,
+            request_id: None,
+            game_id: None,
+        }
+    }
+}
+
+// Zustand einer Suche, den max und min durch die Rekursion reichen. Jeder Suchthread hat einen eigenen
+struct Search<'a> {
+    evaluation_cache: HashMap<GameBoard, i64>,
+    transposition_table: &'a TranspositionTable,
+    player_started: bool,
+    difficulty: &'a Difficulty,
+    cancel: &'a CancelToken,
+    // Ende der Bedenkzeit für Iterationen über calculation_depth hinaus, danach wird wie bei cancel abgebrochen
+    deadline: Option<Instant>,
+    nodes: u64,
+    evaluation_cache_hits: u64,
+    evaluation_cache_misses: u64,
+    tt_probes: u64,
+    tt_hits: u64,
+}
+
+impl<'a> Search<'a> {
+    fn new(
+        transposition_table: &'a TranspositionTable,
+        player_started: bool,
+        difficulty: &'a Difficulty,
+        cancel: &'a CancelToken,
+    ) -> Search<'a> {
+        Search {
+            evaluation_cache: HashMap::new(),
+            transposition_table,
+            player_started,
+            difficulty,
+            cancel,
+            deadline: None,
+            nodes: 0,
+            evaluation_cache_hits: 0,
+            evaluation_cache_misses: 0,
+            tt_probes: 0,
+            tt_hits: 0,
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    fn evaluate(&mut self, game_board_variation: &GameBoard) -> i64 {
+        if let Some(score) = self.evaluation_cache.get(game_board_variation) {
+            self.evaluation_cache_hits += 1;
+            return *score;
+        }
+        self.evaluation_cache_misses += 1;
+        let score = evaluate_position(
+            game_board_variation,
+            COMPUTER_PLAYER,
+            self.player_started,
+            self.difficulty.zugzwang_evaluation,
+        );
+        self.evaluation_cache
+            .insert(game_board_variation.clone(), score);
+        score
+    }
+
+    // Abfrage der Transpositionstabelle, die für die Statistik der Suche gezählt wird
+    fn probe(&mut self, key: u64) -> Option<TtEntry> {
+        self.tt_probes += 1;
+        let entry = self.transposition_table.probe(key);
+        self.tt_hits += entry.is_some() as u64;
+        entry
+    }
+
+    fn position_key(&self, game_board_variation: &GameBoard) -> u64 {
+        position_key(
+            game_board_variation,
+            self.player_started,
+            self.difficulty.zugzwang_evaluation,
+        )
+    }
+}
+
 /* gibt zurück bei übergebener Spielstellung:
     - den besten Zug für de Computer
     - ob mit diesem Zug der Sieg für einen der beiden Spieler einher geht
+    - ob der Computer mit diesem Zug bewusst gepatzt hat (blunders_made: bisherige Patzer in diesem Spiel)
+   Aus dem Score lässt sich mit moves_to_win / moves_to_loss die Distanz zum erzwungenen Spielende ablesen.
+   Alle zufälligen Entscheidungen werden mit dem übergebenen Zufallsgenerator getroffen,
+   damit Züge mit gleichem Seed reproduzierbar sind
 */
-pub fn next_move(
+pub fn next_move<R: Rng>(
     game_board: &mut GameBoard,
     computer_started: bool,
     difficulty: &Difficulty,
-) -> (Option<Field>, i64, NextMoveResult) {
-    let mut evaluation_cache: HashMap<GameBoard, i64> = HashMap::new();
+    blunders_made: u8,
+    rng: &mut R,
+) -> (Option<Field>, i64, NextMoveResult, bool) {
+    next_move_with(
+        game_board,
+        computer_started,
+        difficulty,
+        blunders_made,
+        rng,
+        &SearchOptions::default(),
+    )
+}
 
-    let (mut field, val) = max(
-        difficulty.calculation_depth,
-        MIN_SCORE,
-        MAX_SCORE,
+// wie next_move, aber mit eigenen Sucheinstellungen (z.B. mehreren Threads)
+pub fn next_move_with<R: Rng>(
+    game_board: &mut GameBoard,
+    computer_started: bool,
+    difficulty: &Difficulty,
+    blunders_made: u8,
+    rng: &mut R,
+    options: &SearchOptions,
+) -> (Option<Field>, i64, NextMoveResult, bool) {
+    let start = Instant::now();
+    let mut report = SearchReport::new(difficulty.level, game_board.position(), options);
+    let (field, score, next_move_result, blunder) = search_move(
         game_board,
-        &mut evaluation_cache,
         computer_started,
         difficulty,
+        blunders_made,
+        rng,
+        options,
+        &mut report,
     );
+
+    if let Some(report_callback) = &options.report {
+        report.column = field.map(|field| field.x);
+        report.score = score;
+        report.result = next_move_result;
+        report.blunder = blunder;
+        report.cancelled = options.cancel.is_cancelled();
+        report.elapsed_ms = start.elapsed().as_millis() as u64;
+        report_callback(&report);
+    }
+    (field, score, next_move_result, blunder)
+}
+
+fn search_move<R: Rng>(
+    game_board: &mut GameBoard,
+    computer_started: bool,
+    difficulty: &Difficulty,
+    blunders_made: u8,
+    rng: &mut R,
+    options: &SearchOptions,
+    report: &mut SearchReport,
+) -> (Option<Field>, i64, NextMoveResult, bool) {
+    if let Some(column) = options
+        .opening_book
+        .as_ref()
+        .filter(|_| difficulty.skill.is_perfect())
+        .and_then(|opening_book| opening_book.choose(game_board, rng))
+    {
+        // Stellungen aus dem Eröffnungsbuch sind noch offen, der Zug kann also weder gewinnen noch verlieren
+        report.book = true;
+        let field = game_board.drop_chip(column as usize, COMPUTER_PLAYER);
+        return (field, 0, NextMoveResult::NextMove, false);
+    }
+
+    let own_table;
+    let transposition_table = match &options.transposition_table {
+        Some(transposition_table) => transposition_table.as_ref(),
+        None => {
+            own_table = TranspositionTable::new(options.transposition_table_entries);
+            &own_table
+        }
+    };
+    let mut searches: Vec<Search> = (0..options.threads.max(1))
+        .map(|_| {
+            Search::new(
+                transposition_table,
+                computer_started,
+                difficulty,
+                &options.cancel,
+            )
+        })
+        .collect();
+
+    let (mut field, mut val, depth) =
+        iterative_deepening(game_board, &mut searches, options.progress.as_ref());
+    let search = &mut searches[0];
     let mut next_move_result = NextMoveResult::NextMove;
+    let mut blunder = false;
+
+    if search.is_cancelled() {
+        // nach einem Abbruch wird der beste bisher gefundene Zug ohne weitere Suche gespielt
+    } else if field.is_some() && moves_to_loss(val).is_some() {
+        // in einer verlorenen Stellung zögert der Computer die Niederlage so lange wie möglich hinaus
+        field = Some(best_defence(depth, game_board, search, rng));
+    } else if field.is_some() && !difficulty.skill.is_perfect() {
+        // schwächere Stufen wählen menschenähnlich aus allen bewerteten Zügen
+        let true_scores = root_scores(depth, game_board, search);
+
+        // beim Übersehen von Bedrohungen wird nur der eigene Zug ohne Antwort des Gegners bewertet
+        let perceived_scores = if difficulty.skill.overlooks_threats(rng) {
+            root_scores(1, game_board, search)
+        } else {
+            true_scores.clone()
+        };
+
+        if let Some((chosen, is_blunder)) =
+            difficulty
+                .skill
+                .choose_move(&perceived_scores, &true_scores, blunders_made, rng)
+        {
+            field = Some(chosen);
+            blunder = is_blunder;
+            val = true_scores
+                .iter()
+                .find(|(possible_move, _)| *possible_move == chosen)
+                .map_or(val, |(_, score)| *score);
+        }
+    }
+
+    report.depth = depth;
+    for search in &searches {
+        report.nodes += search.nodes;
+        report.evaluation_cache_hits += search.evaluation_cache_hits;
+        report.evaluation_cache_misses += search.evaluation_cache_misses;
+        report.tt_probes += search.tt_probes;
+        report.tt_hits += search.tt_hits;
+    }
 
-    // wenn ein Sieg für den Gegner bereits entschieden ist, spielt der Computer ein zufälliges Feld
     let free_fields = available_fields(game_board);
-    if field.is_none() && !free_fields.is_empty() {
-        field = Some(*free_fields.choose(&mut rand::thread_rng()).unwrap());
+    if free_fields.is_empty() {
+        return if moves_to_loss(val).is_some() {
+            (None, val, NextMoveResult::PlayerWins, false)
+        } else {
+            (None, 0, NextMoveResult::Draw, false)
+        };
     }
 
-    if free_fields.is_empty() && val != MIN_SCORE {
-        return (None, 0, NextMoveResult::Draw);
+    // ist das Spiel bereits entschieden, spielt der Computer ein zufälliges Feld
+    if field.is_none() {
+        field = Some(*free_fields.choose(rng).unwrap());
     }
 
     game_board.set(field.unwrap().x as usize, field.unwrap().y as usize, 2);
@@ -160,20 +543,529 @@ pub fn next_move(
         next_move_result = NextMoveResult::PlayerWins;
     }
 
-    (field, val, next_move_result)
+    (field, val, next_move_result, blunder)
+}
+
+// Anzahl der eigenen Züge bis zum erzwungenen Sieg, wenn der Score einen Sieg kodiert
+pub fn moves_to_win(score: i64) -> Option<u8> {
+    let plies = MAX_SCORE - score;
+    (0..=MAX_PLIES)
+        .contains(&plies)
+        .then_some(((plies + 1) / 2) as u8)
+}
+
+// Anzahl der gegnerischen Züge bis zur erzwungenen Niederlage, wenn der Score eine Niederlage kodiert
+pub fn moves_to_loss(score: i64) -> Option<u8> {
+    let plies = MAX_SCORE + score;
+    (0..=MAX_PLIES)
+        .contains(&plies)
+        .then_some((plies / 2) as u8)
+}
+
+// rechnet einen Sieg-Score relativ zur aktuellen Stellung in einen Score relativ zur Wurzel der Suche um
+fn mate_score_from_root(score: i64, ply: u8) -> i64 {
+    if score >= MAX_SCORE - MAX_PLIES {
+        score - ply as i64
+    } else if score <= MIN_SCORE + MAX_PLIES {
+        score + ply as i64
+    } else {
+        score
+    }
+}
+
+// Umkehrung von mate_score_from_root, damit Einträge der Transpositionstabelle unabhängig von der Wurzel sind
+fn mate_score_to_node(score: i64, ply: u8) -> i64 {
+    if score >= MAX_SCORE - MAX_PLIES - ply as i64 {
+        score + ply as i64
+    } else if score <= MIN_SCORE + MAX_PLIES + ply as i64 {
+        score - ply as i64
+    } else {
+        score
+    }
+}
+
+/*
+   Wählt in einer verlorenen Stellung unter den Zügen, die die Niederlage am längsten hinauszögern,
+   den Zug, nach dem der Computer die meisten eigenen Bedrohungen (Fallen) hat
+*/
+fn best_defence<R: Rng>(
+    depth: u8,
+    game_board_variation: &mut GameBoard,
+    search: &mut Search,
+    rng: &mut R,
+) -> Field {
+    let scores = root_scores(depth, game_board_variation, search);
+    let best_score = scores.iter().map(|(_, score)| *score).max().unwrap();
+
+    let candidates: Vec<(Field, i64)> = scores
+        .into_iter()
+        .filter(|(_, score)| *score == best_score)
+        .map(|(possible_move, _)| {
+            game_board_variation.set(
+                possible_move.x as usize,
+                possible_move.y as usize,
+                COMPUTER_PLAYER,
+            );
+            let traps = evaluate_threats(
+                &game_board_variation.grid,
+                COMPUTER_PLAYER,
+                4,
+                &mut Vec::new(),
+            );
+            game_board_variation.set(possible_move.x as usize, possible_move.y as usize, 0);
+            (possible_move, traps)
+        })
+        .collect();
+
+    let most_traps = candidates.iter().map(|(_, traps)| *traps).max().unwrap();
+    let best_moves: Vec<Field> = candidates
+        .into_iter()
+        .filter(|(_, traps)| *traps == most_traps)
+        .map(|(possible_move, _)| possible_move)
+        .collect();
+
+    *best_moves.choose(rng).unwrap()
+}
+
+/*
+   Pondering: durchsucht, während der Nutzer überlegt, die Stellungen nach seinen möglichen Antworten,
+   beginnend mit der erwarteten Antwort aus der Transpositionstabelle. Jede Tiefe wird für alle Antworten
+   durchsucht, bevor es tiefer geht. Die Ergebnisse landen in der Transpositionstabelle der Optionen, sodass
+   der Zug nach der tatsächlichen Antwort sofort feststeht bzw. tiefer reicht.
+   Stufen mit Bedenkzeit suchen ohnehin über ihre Tiefe hinaus, für sie rechnet das Pondering bis zum Abbruch
+   durch options.cancel. Bei fester Tiefe endet es mit dieser, tiefere Ergebnisse würden die Stufe stärker machen
+*/
+pub fn ponder(
+    game_board: &GameBoard,
+    computer_started: bool,
+    difficulty: &Difficulty,
+    options: &SearchOptions,
+) {
+    let Some(transposition_table) = options.transposition_table.as_deref() else {
+        return;
+    };
+
+    let mut replies = available_fields(game_board);
+    let predicted = transposition_table
+        .probe(position_key(
+            game_board,
+            computer_started,
+            difficulty.zugzwang_evaluation,
+        ))
+        .and_then(|entry| entry.best_move);
+    order_moves(&mut replies, predicted);
+
+    // Antworten, mit denen der Nutzer gewinnt, erfordern keinen Zug der Engine
+    let mut positions: Vec<GameBoard> = replies
+        .iter()
+        .map(|reply| {
+            let mut game_board_variation = game_board.clone();
+            game_board_variation.set(reply.x as usize, reply.y as usize, USER_PLAYER);
+            game_board_variation
+        })
+        .filter(|game_board_variation| !check_for_row(&game_board_variation.grid, USER_PLAYER, 4).0)
+        .collect();
+
+    let max_depth = match difficulty.time_limit {
+        Some(_) => MAX_PLIES as u8,
+        None => difficulty.calculation_depth,
+    };
+    for depth in 1..=max_depth {
+        let mut decided = Vec::new();
+        for (index, game_board_variation) in positions.iter_mut().enumerate() {
+            if options.cancel.is_cancelled() {
+                return;
+            }
+
+            let mut searches: Vec<Search> = (0..options.threads.max(1))
+                .map(|_| {
+                    Search::new(
+                        transposition_table,
+                        computer_started,
+                        difficulty,
+                        &options.cancel,
+                    )
+                })
+                .collect();
+            let val = if searches.len() > 1 {
+                parallel_root(depth, game_board_variation, &mut searches).1
+            } else {
+                max(
+                    depth,
+                    0,
+                    MIN_SCORE,
+                    MAX_SCORE,
+                    game_board_variation,
+                    &mut searches[0],
+                )
+                .1
+            };
+
+            // ein erzwungener Sieg oder eine erzwungene Niederlage ändert sich mit größerer Tiefe nicht mehr
+            if moves_to_win(val).is_some() || moves_to_loss(val).is_some() {
+                decided.push(index);
+            }
+        }
+        for index in decided.into_iter().rev() {
+            positions.remove(index);
+        }
+    }
+}
+
+/*
+   Bewertung aller möglichen Züge einer Stellung aus Sicht des Computers
+    - depth: Tiefe der letzten vollständigen Iteration, mit der alle Züge bewertet wurden
+    - scores: Spalte und Score jedes möglichen Zugs, von links nach rechts
+    - pv: Hauptvariante beginnend mit dem besten Zug
+*/
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MoveAnalysis {
+    pub depth: u8,
+    pub scores: Vec<(u8, i64)>,
+    pub best_column: u8,
+    pub best_score: i64,
+    pub pv: Vec<u8>,
+    pub nodes: u64,
+}
+
+impl MoveAnalysis {
+    pub fn score_of(&self, column: u8) -> Option<i64> {
+        self.scores
+            .iter()
+            .find(|(possible_column, _)| *possible_column == column)
+            .map(|(_, score)| *score)
+    }
+}
+
+/*
+   Analysiert eine Stellung, z.B. für die Nachbesprechung eines Spiels: nach der iterativen Suche wird jeder
+   Zug mit der erreichten Tiefe und vollem Suchfenster bewertet. Anders als bei next_move gibt es weder
+   Eröffnungsbuch noch Schwächen der Stufe. None, wenn die Stellung bereits entschieden ist oder die Suche
+   abgebrochen wurde, da die Scores eines abgebrochenen Durchlaufs nicht vergleichbar sind
+*/
+pub fn analyse_moves(
+    game_board: &GameBoard,
+    computer_started: bool,
+    difficulty: &Difficulty,
+    options: &SearchOptions,
+) -> Option<MoveAnalysis> {
+    if available_fields(game_board).is_empty()
+        || check_for_row(&game_board.grid, COMPUTER_PLAYER, 4).0
+        || check_for_row(&game_board.grid, USER_PLAYER, 4).0
+    {
+        return None;
+    }
+
+    let own_table;
+    let transposition_table = match &options.transposition_table {
+        Some(transposition_table) => transposition_table.as_ref(),
+        None => {
+            own_table = TranspositionTable::new(options.transposition_table_entries);
+            &own_table
+        }
+    };
+    let mut searches: Vec<Search> = (0..options.threads.max(1))
+        .map(|_| {
+            Search::new(
+                transposition_table,
+                computer_started,
+                difficulty,
+                &options.cancel,
+            )
+        })
+        .collect();
+
+    let mut game_board_variation = game_board.clone();
+    let (_, _, depth) = iterative_deepening(&mut game_board_variation, &mut searches, None);
+    let scores = root_scores(depth, &mut game_board_variation, &mut searches[0]);
+    if options.cancel.is_cancelled() {
+        return None;
+    }
+
+    // wie bei der Suche gilt der erste Zug mit dem besten Score als bester Zug
+    let (best_field, best_score) = scores.iter().fold(scores[0], |best, &(field, score)| {
+        if score > best.1 {
+            (field, score)
+        } else {
+            best
+        }
+    });
+    let mut column_scores: Vec<(u8, i64)> = scores
+        .iter()
+        .map(|(field, score)| (field.x, *score))
+        .collect();
+    column_scores.sort_unstable();
+    Some(MoveAnalysis {
+        depth,
+        scores: column_scores,
+        best_column: best_field.x,
+        best_score,
+        pv: principal_variation(game_board, best_field, &searches[0], depth),
+        nodes: searches.iter().map(|search| search.nodes).sum(),
+    })
+}
+
+/*
+   Sucht mit steigender Tiefe bis zur Tiefe der Schwierigkeit. Hat sie eine Bedenkzeit, wird danach tiefer
+   gesucht, bis die Bedenkzeit abgelaufen ist; eine solche zusätzliche Iteration wird bei Ablauf abgebrochen.
+   Wird die Suche abgebrochen, gilt das Ergebnis der letzten vollständigen Iteration (Tiefe 1 wird immer beendet).
+   Mit mehr als einer Suche werden die Züge an der Wurzel parallel durchsucht.
+   Gibt den besten Zug, dessen Score und die erreichte Tiefe zurück
+*/
+fn iterative_deepening(
+    game_board: &mut GameBoard,
+    searches: &mut [Search],
+    progress: Option<&ProgressCallback>,
+) -> (Option<Field>, i64, u8) {
+    let difficulty = searches[0].difficulty;
+    let start = Instant::now();
+    let deadline = difficulty.time_limit.map(|time_limit| start + time_limit);
+    let mut depth = 1;
+    let mut completed = (None, MIN_SCORE, 0);
+
+    loop {
+        if depth > difficulty.calculation_depth {
+            for search in searches.iter_mut() {
+                search.deadline = deadline;
+            }
+        }
+        let (field, val) = if searches.len() > 1 {
+            parallel_root(depth, game_board, searches)
+        } else {
+            max(depth, 0, MIN_SCORE, MAX_SCORE, game_board, &mut searches[0])
+        };
+
+        if depth > 1 && searches[0].is_cancelled() {
+            break;
+        }
+        completed = (field, val, depth);
+
+        if let Some(progress) = progress {
+            progress(&SearchInfo {
+                depth,
+                best_column: field.map(|field| field.x),
+                score: val,
+                nodes: searches.iter().map(|search| search.nodes).sum(),
+                pv: field.map_or_else(Vec::new, |field| {
+                    principal_variation(game_board, field, &searches[0], depth)
+                }),
+                elapsed_ms: start.elapsed().as_millis() as u64,
+            });
+        }
+
+        // ein erzwungener Sieg oder eine erzwungene Niederlage ändert sich mit größerer Tiefe nicht mehr
+        let decided = moves_to_win(val).is_some() || moves_to_loss(val).is_some();
+        let time_left = deadline.is_some_and(|deadline| Instant::now() < deadline);
+
+        if decided
+            || depth as i64 >= MAX_PLIES
+            || (depth >= difficulty.calculation_depth && !time_left)
+        {
+            break;
+        }
+        depth += 1;
+    }
+
+    // die Bedenkzeit gilt nur für die Iterationen, die Auswertung des Ergebnisses wird nicht mehr abgebrochen
+    for search in searches.iter_mut() {
+        search.deadline = None;
+    }
+    completed
+}
+
+/*
+   Liest die Hauptvariante aus der Transpositionstabelle: nach dem besten Zug des Computers
+   werden abwechselnd die dort gespeicherten besten Züge beider Seiten gespielt
+*/
+fn principal_variation(
+    game_board: &GameBoard,
+    first: Field,
+    search: &Search,
+    depth: u8,
+) -> Vec<u8> {
+    let mut game_board_variation = game_board.clone();
+    game_board_variation.set(first.x as usize, first.y as usize, COMPUTER_PLAYER);
+    let mut pv = vec![first.x];
+    let mut player = USER_PLAYER;
+
+    while pv.len() < depth as usize
+        && !check_for_row(&game_board_variation.grid, other_player(player), 4).0
+    {
+        let key = search.position_key(&game_board_variation);
+        let Some(column) = search
+            .transposition_table
+            .probe(key)
+            .and_then(|entry| entry.best_move)
+        else {
+            break;
+        };
+        if game_board_variation
+            .drop_chip(column as usize, player)
+            .is_none()
+        {
+            break;
+        }
+        pv.push(column);
+        player = other_player(player);
+    }
+
+    pv
+}
+
+/*
+   Verteilt die Züge an der Wurzel auf mehrere Threads. Jeder Thread nimmt sich den nächsten noch nicht
+   durchsuchten Zug, über die gemeinsame untere Schranke (alpha) profitieren alle Threads von den bereits
+   bewerteten Zügen. Die Schranke liegt knapp unter dem bisher besten Score, damit gleich gute Züge exakt
+   bewertet werden. Wie bei der sequentiellen Suche wird der erste Zug mit dem besten Score gewählt,
+   das Ergebnis hängt also nicht davon ab, in welcher Reihenfolge die Threads fertig werden
+*/
+fn parallel_root(
+    depth: u8,
+    game_board: &GameBoard,
+    searches: &mut [Search],
+) -> (Option<Field>, i64) {
+    let possible_moves = available_fields(game_board);
+    if possible_moves.is_empty()
+        || check_for_row(&game_board.grid, COMPUTER_PLAYER, 4).0
+        || check_for_row(&game_board.grid, USER_PLAYER, 4).0
+    {
+        return max(
+            depth,
+            0,
+            MIN_SCORE,
+            MAX_SCORE,
+            &mut game_board.clone(),
+            &mut searches[0],
+        );
+    }
+
+    let next_index = AtomicUsize::new(0);
+    let alpha = AtomicI64::new(MIN_SCORE);
+    let scores = Mutex::new(vec![MIN_SCORE; possible_moves.len()]);
+
+    thread::scope(|scope| {
+        for search in searches.iter_mut() {
+            let (next_index, alpha, scores, possible_moves) =
+                (&next_index, &alpha, &scores, &possible_moves);
+
+            scope.spawn(move || {
+                let mut game_board_variation = game_board.clone();
+                loop {
+                    let index = next_index.fetch_add(1, Ordering::Relaxed);
+                    let Some(possible_move) = possible_moves.get(index) else {
+                        break;
+                    };
+
+                    let lower_bound = match alpha.load(Ordering::Relaxed) {
+                        MIN_SCORE => MIN_SCORE,
+                        best => best - 1,
+                    };
+
+                    game_board_variation.set(
+                        possible_move.x as usize,
+                        possible_move.y as usize,
+                        COMPUTER_PLAYER,
+                    ); // führe Zug aus
+                    let val = min(
+                        depth - 1,
+                        1,
+                        lower_bound,
+                        MAX_SCORE,
+                        &mut game_board_variation,
+                        search,
+                    )
+                    .1;
+                    game_board_variation.set(possible_move.x as usize, possible_move.y as usize, 0); // mache Zug rückgängig
+
+                    alpha.fetch_max(val, Ordering::Relaxed);
+                    scores.lock().unwrap()[index] = val;
+                }
+            });
+        }
+    });
+
+    let scores = scores.into_inner().unwrap();
+    let best_score = *scores.iter().max().unwrap();
+    if best_score <= MIN_SCORE {
+        return (None, MIN_SCORE);
+    }
+    let best_index = scores
+        .iter()
+        .position(|score| *score == best_score)
+        .unwrap();
+    (Some(possible_moves[best_index]), best_score)
+}
+
+/*
+   Bewertet jeden möglichen Zug des Computers einzeln mit vollem Suchfenster,
+   damit die Scores der Züge untereinander vergleichbar sind
+*/
+fn root_scores(
+    depth: u8,
+    game_board_variation: &mut GameBoard,
+    search: &mut Search,
+) -> Vec<(Field, i64)> {
+    let mut scores = Vec::new();
+
+    for possible_move in available_fields(game_board_variation) {
+        game_board_variation.set(
+            possible_move.x as usize,
+            possible_move.y as usize,
+            COMPUTER_PLAYER,
+        ); // führe Zug aus
+
+        let val = min(
+            depth - 1,
+            1,
+            MIN_SCORE,
+            MAX_SCORE,
+            game_board_variation,
+            search,
+        )
+        .1;
+
+        game_board_variation.set(possible_move.x as usize, possible_move.y as usize, 0); // mache Zug rückgängig
+        scores.push((possible_move, val));
+    }
+
+    scores
+}
+
+// Score eines Eintrags der Transpositionstabelle, wenn er für Tiefe und Suchfenster ausreicht
+fn transposition_cutoff(entry: &TtEntry, depth: u8, ply: u8, alpha: i64, beta: i64) -> Option<i64> {
+    if entry.depth < depth {
+        return None;
+    }
+
+    let score = mate_score_from_root(entry.score, ply);
+    match entry.bound {
+        Bound::Exact => Some(score),
+        Bound::Lower if score >= beta => Some(score),
+        Bound::Upper if score <= alpha => Some(score),
+        _ => None,
+    }
+}
+
+// zieht den besten Zug einer früheren Suche nach vorne, die übrigen Züge behalten ihre Reihenfolge
+fn order_moves(possible_moves: &mut [Field], best_move: Option<u8>) {
+    if let Some(index) =
+        best_move.and_then(|column| possible_moves.iter().position(|field| field.x == column))
+    {
+        possible_moves[..=index].rotate_right(1);
+    }
 }
 
 fn max(
     depth: u8,
+    ply: u8,
     alpha: i64,
     beta: i64,
     game_board_variation: &mut GameBoard,
-    evaluation_cache: &mut HashMap<GameBoard, i64>,
-    player_started: bool,
-    difficulty: &Difficulty,
+    search: &mut Search,
 ) -> (Option<Field>, i64) {
     let mut result = None;
-    let possible_moves = available_fields(game_board_variation); // Liste aller möglichen Züge
+    let mut possible_moves = available_fields(game_board_variation); // Liste aller möglichen Züge
+    search.nodes += 1;
 
     /* breche die Rekursion ab und berechne den Score der aktuellen Spielstellung,
     wenn die maximale Tiefe erreicht ist, oder einer der beiden Spieler das Spiel gewonnen hat
@@ -183,16 +1075,26 @@ fn max(
         || check_for_row(&game_board_variation.grid, COMPUTER_PLAYER, 4).0
         || check_for_row(&game_board_variation.grid, USER_PLAYER, 4).0
     {
-        return (
-            None,
-            evaluation(
-                game_board_variation,
-                evaluation_cache,
-                COMPUTER_PLAYER,
-                player_started,
-                difficulty.zugzwang_evaluation,
-            ),
-        );
+        let score = search.evaluate(game_board_variation);
+        return (None, mate_score_from_root(score, ply));
+    }
+
+    // eine abgebrochene Suche wird nicht fortgesetzt, ihre Scores werden verworfen
+    if ply > 0 && search.is_cancelled() {
+        return (None, alpha);
+    }
+
+    /* auf höchster Ebene wird immer gesucht, damit ein Zug zurückgegeben wird.
+    Die Reihenfolge der Züge bleibt dort unverändert, damit bei gleichem Score derselbe Zug gewählt wird
+    */
+    let key = search.position_key(game_board_variation);
+    if ply > 0 {
+        if let Some(entry) = search.probe(key) {
+            if let Some(score) = transposition_cutoff(&entry, depth, ply, alpha, beta) {
+                return (None, score);
+            }
+            order_moves(&mut possible_moves, entry.best_move);
+        }
     }
 
     // der Score des besten Zugs für den maximierenden Spieler (Computer)
@@ -207,12 +1109,11 @@ fn max(
 
         let val = min(
             depth - 1,
+            ply + 1,
             max_val,
             beta,
             game_board_variation,
-            evaluation_cache,
-            player_started,
-            difficulty,
+            search,
         )
         .1;
 
@@ -223,9 +1124,7 @@ fn max(
             max_val = val;
 
             // auf höchster Ebene ist der beste gefundene Zug der, der am Ende zurückgegeben wird
-            if depth == difficulty.calculation_depth {
-                result = Some(possible_move);
-            }
+            result = Some(possible_move);
 
             // Alpha-Beta-Pruning
             if max_val >= beta {
@@ -234,20 +1133,42 @@ fn max(
         }
     }
 
+    // unvollständige Ergebnisse dürfen nicht in die Transpositionstabelle gelangen
+    if search.is_cancelled() {
+        return (result, max_val);
+    }
+
+    let bound = if max_val >= beta {
+        Bound::Lower
+    } else if result.is_none() {
+        Bound::Upper
+    } else {
+        Bound::Exact
+    };
+    search.transposition_table.store(
+        key,
+        TtEntry {
+            depth,
+            score: mate_score_to_node(max_val, ply),
+            bound,
+            best_move: result.map(|field| field.x),
+        },
+    );
+
     // gib den maximalen Zug-Score für die aktuelle Ebene zurück und auf der höchsten Ebene ebenfalls den dazugehörigen Zug
     (result, max_val)
 }
 
 fn min(
     depth: u8,
+    ply: u8,
     alpha: i64,
     beta: i64,
     game_board_variation: &mut GameBoard,
-    evaluation_cache: &mut HashMap<GameBoard, i64>,
-    player_started: bool,
-    difficulty: &Difficulty,
+    search: &mut Search,
 ) -> (Option<Field>, i64) {
-    let possible_moves = available_fields(game_board_variation); // Liste aller möglichen Züge
+    let mut possible_moves = available_fields(game_board_variation); // Liste aller möglichen Züge
+    search.nodes += 1;
 
     /* breche die Rekursion ab und berechne den Score der aktuellen Spielstellung,
     wenn die maximale Tiefe erreicht ist, oder einer der beiden Spieler das Spiel gewonnen hat
@@ -257,20 +1178,25 @@ fn min(
         || check_for_row(&game_board_variation.grid, COMPUTER_PLAYER, 4).0
         || check_for_row(&game_board_variation.grid, USER_PLAYER, 4).0
     {
-        return (
-            None,
-            evaluation(
-                game_board_variation,
-                evaluation_cache,
-                COMPUTER_PLAYER,
-                player_started,
-                difficulty.zugzwang_evaluation,
-            ),
-        );
+        let score = search.evaluate(game_board_variation);
+        return (None, mate_score_from_root(score, ply));
+    }
+
+    if search.is_cancelled() {
+        return (None, beta);
+    }
+
+    let key = search.position_key(game_board_variation);
+    if let Some(entry) = search.probe(key) {
+        if let Some(score) = transposition_cutoff(&entry, depth, ply, alpha, beta) {
+            return (None, score);
+        }
+        order_moves(&mut possible_moves, entry.best_move);
     }
 
     // der Score des besten Zugs für den minimierenden Spieler (Gegner des Computers)
     let mut min_val = beta;
+    let mut best_move = None;
 
     for possible_move in possible_moves {
         game_board_variation.set(
@@ -280,12 +1206,11 @@ fn min(
         ); // führe Zug aus
         let val = max(
             depth - 1,
+            ply + 1,
             alpha,
             min_val,
             game_board_variation,
-            evaluation_cache,
-            player_started,
-            difficulty,
+            search,
         )
         .1;
         game_board_variation.set(possible_move.x as usize, possible_move.y as usize, 0); // mache Zug rückgängig
@@ -293,6 +1218,7 @@ fn min(
         // ein besserer Zug wurde gefunden
         if val < min_val {
             min_val = val;
+            best_move = Some(possible_move.x);
 
             // Alpha-Beta-Pruning
             if min_val <= alpha {
@@ -301,6 +1227,27 @@ fn min(
         }
     }
 
+    if search.is_cancelled() {
+        return (None, min_val);
+    }
+
+    let bound = if min_val <= alpha {
+        Bound::Upper
+    } else if best_move.is_none() {
+        Bound::Lower
+    } else {
+        Bound::Exact
+    };
+    search.transposition_table.store(
+        key,
+        TtEntry {
+            depth,
+            score: mate_score_to_node(min_val, ply),
+            bound,
+            best_move,
+        },
+    );
+
     // gib den minimalen Zug-Score für die aktuelle Ebene zurück
     (None, min_val)
 }
@@ -314,13 +1261,29 @@ pub fn evaluation(
     zugzwang_evaluation: bool,
 ) -> i64 {
     // wenn ein score für diese Spielstellung bereits berechnet wurde, gib diesen zurück und berechne ihn nicht neu
-    match evaluation_cache.get(game_board_variation) {
-        None => {}
-        Some(ev) => {
-            return *ev;
-        }
+    if let Some(ev) = evaluation_cache.get(game_board_variation) {
+        return *ev;
     }
 
+    let result = evaluate_position(
+        game_board_variation,
+        player,
+        player_started,
+        zugzwang_evaluation,
+    );
+
+    // füge den berechneten Score in den Cache ein
+    evaluation_cache.insert(game_board_variation.clone(), result);
+    result
+}
+
+// Bewertung einer Spielposition ohne Cache, siehe evaluation
+fn evaluate_position(
+    game_board_variation: &GameBoard,
+    player: u8,
+    player_started: bool,
+    zugzwang_evaluation: bool,
+) -> i64 {
     // Liste aller Zugzwänge
     let mut zugzwang_list: Vec<Zugzwang> = Vec::new();
 
@@ -331,9 +1294,9 @@ pub fn evaluation(
         &mut zugzwang_list,
     );
 
-    // catch possible integer overflow
+    // gewonnene Stellungen werden als Sieg in 0 Halbzügen bewertet, die Suche rechnet die Distanz zur Wurzel hinzu
     if min_ev == MAX_SCORE {
-        return -MAX_SCORE;
+        return MIN_SCORE;
     }
 
     if max_ev == MAX_SCORE {
@@ -347,9 +1310,6 @@ pub fn evaluation(
         result += evaluate_zugzwang_positions(zugzwang_list, player, player_started) as i64
             * ZUGZWANG_SCORE;
     }
-
-    // füge den berechneten Score in den Cache ein
-    evaluation_cache.insert(game_board_variation.clone(), result);
     result
 }
 
@@ -765,6 +1725,7 @@ fn evaluate_turns(rules: Vec<(i8, bool, bool)>, best_move: &mut (usize, i8), x:
    Simuliert regelbasiert den Zug des übergebenen Spielers
    Gibt 0 bei einem nicht-finalem Zug, -1 bei Niederlage für den Spieler, 1 bei Sieg für ihn und 3 bei einem Unentschieden zurück
 */
+#[allow(clippy::ptr_arg)] // ursprüngliche Signatur, erst neuere clippy-Versionen verlangen hier einen Slice
 fn simulate_zugzwang_turn(
     board: &mut Vec<Vec<u8>>,
     player: u8,
@@ -802,7 +1763,7 @@ fn simulate_zugzwang_turn(
                 // Decke eigenen ungeraden Zugzwang auf ((y + 2) da y unten bei 0 startet)
                 (
                     2,
-                    y + 1 < column.len() && column[y + 1] == player && (y + 2) % 2 != 0,
+                    y + 1 < column.len() && column[y + 1] == player && !(y + 2).is_multiple_of(2),
                     true,
                 ),
                 // Decke eigenen Zugzwang auf
@@ -810,7 +1771,7 @@ fn simulate_zugzwang_turn(
                 // Spiele 2 Felder unter eigenen ungeraden Zugzwang (erlange Zugzwang-Kontrolle)
                 (
                     5,
-                    y + 2 < column.len() && column[y + 2] == player && (y + 1) % 2 == 0,
+                    y + 2 < column.len() && column[y + 2] == player && (y + 1).is_multiple_of(2),
                     false,
                 ),
                 // Spiele 2 Felder unter eigenen Zugzwang
@@ -866,6 +1827,7 @@ fn simulate_zugzwang_turn(
 }
 
 // checks if the column is empty y upwards
+#[allow(clippy::needless_range_loop)] // ursprünglicher Code, erst neuere clippy-Versionen bemängeln die Schleife
 fn column_is_empty(column: &[u8], y: usize) -> bool {
     for i in y..column.len() {
         if column[i] != 0 {
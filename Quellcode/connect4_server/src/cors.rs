@@ -0,0 +1,84 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use actix_cors::Cors;
+use actix_web::http::header::HeaderName;
+use actix_web::http::Method;
+
+use crate::access::API_KEY_HEADER;
+use crate::logging::REQUEST_ID_HEADER;
+
+// Ursprung der Electron-Oberfläche, die ihre Seite aus einer lokalen Datei lädt
+pub const ELECTRON_ORIGIN: &str = "file://";
+pub const DEFAULT_METHODS: [&str; 3] = ["GET", "POST", "DELETE"];
+pub const DEFAULT_HEADERS: [&str; 3] = ["Content-Type", API_KEY_HEADER, REQUEST_ID_HEADER];
+
+/*
+   Welche fremden Seiten den Server aus dem Browser heraus ansprechen dürfen
+    - origins: exakt verglichene Ursprünge wie "file://" oder "http://localhost:1212"
+    - permissive: erlaubt jeden Ursprung, jede Methode und jeden Header (nur für die Entwicklung)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorsPolicy {
+    pub permissive: bool,
+    pub origins: Vec<String>,
+    pub methods: Vec<String>,
+    pub headers: Vec<String>,
+}
+
+impl Default for CorsPolicy {
+    fn default() -> Self {
+        CorsPolicy {
+            permissive: false,
+            origins: vec![ELECTRON_ORIGIN.to_string()],
+            methods: DEFAULT_METHODS.map(String::from).to_vec(),
+            headers: DEFAULT_HEADERS.map(String::from).to_vec(),
+        }
+    }
+}
+
+impl CorsPolicy {
+    // ungültige Einträge würden sonst erst beim Erzeugen der Middleware in jedem Worker auffallen
+    pub fn validate(&self) -> Result<(), String> {
+        for origin in &self.origins {
+            if origin == "*" {
+                return Err("cors origin '*' is not allowed, use the permissive mode".to_string());
+            }
+            let valid = origin
+                .split_once("://")
+                .is_some_and(|(scheme, host)| !scheme.is_empty() && !host.contains('/'));
+            if !valid {
+                return Err(format!(
+                    "invalid cors origin '{origin}', expected scheme://host[:port] without path"
+                ));
+            }
+        }
+        for method in &self.methods {
+            Method::from_bytes(method.as_bytes())
+                .map_err(|_| format!("invalid cors method '{method}'"))?;
+        }
+        for header in &self.headers {
+            HeaderName::try_from(header.as_str())
+                .map_err(|_| format!("invalid cors header '{header}'"))?;
+        }
+        Ok(())
+    }
+
+    pub fn middleware(&self) -> Cors {
+        if self.permissive {
+            return Cors::permissive();
+        }
+
+        let origins: Arc<HashSet<String>> = Arc::new(self.origins.iter().cloned().collect());
+        Cors::default()
+            .allowed_origin_fn(move |origin, _| {
+                origin.to_str().is_ok_and(|origin| origins.contains(origin))
+            })
+            .allowed_methods(self.methods.iter().map(String::as_str))
+            .allowed_headers(self.headers.iter().map(String::as_str))
+            // damit die Oberfläche die Id der Anfrage für Fehlermeldungen auslesen kann
+            .expose_headers([REQUEST_ID_HEADER])
+    }
+}
@@ -0,0 +1,382 @@
+use actix_web::http::header;
+use actix_web::web::Json;
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+
+use crate::cancellation::{CancelGuard, CancelToken, SearchRegistry};
+use crate::config::Config;
+use crate::connect4ai::{Difficulty, SearchOptions};
+use crate::history::{GameHistory, HistoryQuery};
+use crate::logging::RequestId;
+use crate::metrics::Metrics;
+use crate::multiplayer::MatchStore;
+use crate::notation::GameNotation;
+use crate::pool::ComputePool;
+use crate::rating::PlayerRating;
+use crate::review::{review_game, DEFAULT_REVIEW_LEVEL};
+use crate::session::{GameError, SessionStore};
+use crate::{pool_error_response, ErrorResponse};
+
+#[derive(Debug, Deserialize)]
+pub struct NewGameInfo {
+    player: String,
+    #[serde(default)]
+    computer_started: bool,
+    // ohne Stufe wird die Stufe anhand des Spieler-Ratings gewählt
+    level: Option<u8>,
+    // Seed der Engine, damit das Spiel reproduziert werden kann
+    seed: Option<u64>,
+    // die Engine rechnet weiter, während der Spieler überlegt
+    #[serde(default)]
+    ponder: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MoveInfo {
+    column: u8,
+}
+
+/*
+   Spiel für die Nachbesprechung, entweder als Liste der Züge (Spalten 0-6, der erste Zug ist Rot)
+   oder als Id eines laufenden bzw. im Verlauf gespeicherten Spiels
+*/
+#[derive(Debug, Deserialize)]
+pub struct ReviewInfo {
+    moves: Option<Vec<u8>>,
+    game_id: Option<String>,
+    level: Option<u8>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayerInfo {
+    player: String,
+    #[serde(flatten)]
+    rating: PlayerRating,
+    next_level: u8,
+}
+
+fn error_response(error: GameError) -> HttpResponse {
+    let body = ErrorResponse::new(&error);
+    match error {
+        GameError::NotFound(_) => HttpResponse::NotFound().json(body),
+        GameError::GameOver
+        | GameError::NothingToUndo
+        | GameError::NoTakebacksLeft(_)
+        | GameError::EngineThinking => HttpResponse::Conflict().json(body),
+        GameError::InvalidColumn(_)
+        | GameError::ColumnFull(_)
+        | GameError::Difficulty(_)
+        | GameError::InvalidName => HttpResponse::BadRequest().json(body),
+    }
+}
+
+/*
+   Beginnt der Computer, wird sein erster Zug wie jeder andere Zug der Engine im Rechen-Pool berechnet.
+   Nach dem Timeout spielt die Engine den besten bisher gefundenen Zug, wartet die Aufgabe dann noch, wird
+   kein Spiel angelegt
+*/
+#[post("/games")]
+async fn create_game(
+    store: web::Data<SessionStore>,
+    pool: web::Data<ComputePool>,
+    info: Json<NewGameInfo>,
+    request_id: RequestId,
+) -> impl Responder {
+    let info = info.into_inner();
+    let cancel = CancelToken::new();
+    let search_cancel = cancel.clone();
+    let created = pool
+        .run_cancellable(
+            move || {
+                store.create_game_cancellable(
+                    &info.player,
+                    info.computer_started,
+                    info.level,
+                    info.seed,
+                    info.ponder,
+                    Some(&request_id.0),
+                    search_cancel,
+                )
+            },
+            || cancel.cancel(),
+        )
+        .await;
+    match created {
+        Ok(Ok(game)) => HttpResponse::Created().json(game),
+        Ok(Err(error)) => error_response(error),
+        Err(error) => pool_error_response(error),
+    }
+}
+
+#[get("/games/{id}")]
+async fn get_game(store: web::Data<SessionStore>, id: web::Path<String>) -> impl Responder {
+    match store.game(&id) {
+        Ok(game) => HttpResponse::Ok().json(game),
+        Err(error) => error_response(error),
+    }
+}
+
+#[post("/games/{id}/moves")]
+async fn play_move(
+    store: web::Data<SessionStore>,
+    pool: web::Data<ComputePool>,
+    metrics: web::Data<Metrics>,
+    id: web::Path<String>,
+    info: Json<MoveInfo>,
+    request_id: RequestId,
+) -> impl Responder {
+    let column = info.column;
+    let (search_store, search_id) = (store.clone(), id.clone());
+    // nach dem Timeout spielt die Engine den besten bisher gefundenen Zug, ein noch wartender Zug entfällt
+    let played = pool
+        .run_cancellable(
+            move || store.play(&id, column, Some(&request_id.0)),
+            || {
+                search_store.cancel_search(&search_id);
+            },
+        )
+        .await;
+    match played {
+        // ein beendetes Spiel nimmt keine Züge mehr an, jedes Spielende wird also genau einmal gezählt
+        Ok(Ok(game)) => {
+            metrics.record_game_finished(game.result);
+            HttpResponse::Ok().json(game)
+        }
+        Ok(Err(error)) => error_response(error),
+        Err(error) => pool_error_response(error),
+    }
+}
+
+/*
+   Nimmt den letzten Zug des Spielers und die Antwort der Engine zurück, begrenzt je Stufe. Auch nach einer
+   Niederlage möglich, das Spiel wird dabei aus dem Verlauf entfernt
+*/
+#[post("/games/{id}/undo")]
+async fn undo_move(store: web::Data<SessionStore>, id: web::Path<String>) -> impl Responder {
+    match web::block(move || store.undo(&id)).await {
+        Ok(Ok(game)) => HttpResponse::Ok().json(game),
+        Ok(Err(error)) => error_response(error),
+        Err(error) => HttpResponse::InternalServerError().json(ErrorResponse::new(error)),
+    }
+}
+
+// beendet das Spiel vorzeitig, ein laufender Zug der Engine wird abgebrochen
+#[delete("/games/{id}")]
+async fn remove_game(store: web::Data<SessionStore>, id: web::Path<String>) -> impl Responder {
+    match store.remove_game(&id) {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(error) => error_response(error),
+    }
+}
+
+// Rating des Spielers und die Stufe, die für sein nächstes Spiel gewählt würde
+#[get("/players/{player}")]
+async fn get_player(store: web::Data<SessionStore>, player: web::Path<String>) -> impl Responder {
+    let rating = store.rating(&player);
+    HttpResponse::Ok().json(PlayerInfo {
+        player: player.into_inner(),
+        rating,
+        next_level: rating.adaptive_level(),
+    })
+}
+
+// ohne konfigurierte Datenbank gibt es keinen Verlauf
+fn history_disabled() -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorResponse::new(
+        "game history is disabled, start the server with --history-db",
+    ))
+}
+
+// beendete Spiele aus dem Verlauf, neueste zuerst; mit ?player= nur die Spiele eines Spielers
+#[get("/history")]
+async fn list_history(
+    history: Option<web::Data<GameHistory>>,
+    query: web::Query<HistoryQuery>,
+) -> impl Responder {
+    let Some(history) = history else {
+        return history_disabled();
+    };
+    let query = query.into_inner();
+    match web::block(move || history.list(&query)).await {
+        Ok(Ok(games)) => HttpResponse::Ok().json(games),
+        Ok(Err(error)) => HttpResponse::InternalServerError().json(ErrorResponse::new(error)),
+        Err(error) => HttpResponse::InternalServerError().json(ErrorResponse::new(error)),
+    }
+}
+
+// ein beendetes Spiel mit allen Zügen und den Scores der Engine
+#[get("/history/{id}")]
+async fn get_history(
+    history: Option<web::Data<GameHistory>>,
+    id: web::Path<String>,
+) -> impl Responder {
+    let Some(history) = history else {
+        return history_disabled();
+    };
+    let search_id = id.clone();
+    match web::block(move || history.get(&search_id)).await {
+        Ok(Ok(Some(game))) => HttpResponse::Ok().json(game),
+        Ok(Ok(None)) => {
+            HttpResponse::NotFound().json(ErrorResponse::new(format!("game {id} not found")))
+        }
+        Ok(Err(error)) => HttpResponse::InternalServerError().json(ErrorResponse::new(error)),
+        Err(error) => HttpResponse::InternalServerError().json(ErrorResponse::new(error)),
+    }
+}
+
+// Aufzeichnung als Textdatei zum Herunterladen, beendete Spiele werden auch im Verlauf gesucht
+#[get("/games/{id}/record")]
+async fn get_record(
+    store: web::Data<SessionStore>,
+    history: Option<web::Data<GameHistory>>,
+    id: web::Path<String>,
+) -> impl Responder {
+    let notation = match (store.game(&id), history) {
+        (Ok(game), _) => GameNotation::from_game(&game),
+        (Err(error), None) => return error_response(error),
+        (Err(error), Some(history)) => {
+            let search_id = id.clone();
+            match web::block(move || history.get(&search_id)).await {
+                Ok(Ok(Some(record))) => GameNotation::from_record(&record),
+                Ok(Ok(None)) => return error_response(error),
+                Ok(Err(error)) => {
+                    return HttpResponse::InternalServerError().json(ErrorResponse::new(error))
+                }
+                Err(error) => {
+                    return HttpResponse::InternalServerError().json(ErrorResponse::new(error))
+                }
+            }
+        }
+    };
+    HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"connect4-{id}.txt\""),
+        ))
+        .body(notation.to_string())
+}
+
+/*
+   Importiert eine Aufzeichnung (z.B. von einer anderen Installation) in den Verlauf. Die Züge werden
+   auf einem GameBoard nachgespielt, nur vollständige und gültige Partien werden gespeichert
+*/
+#[post("/history")]
+async fn import_record(history: Option<web::Data<GameHistory>>, body: String) -> impl Responder {
+    let Some(history) = history else {
+        return history_disabled();
+    };
+    let id = format!("{:016x}", rand::random::<u64>());
+    let record = match GameNotation::parse(&body).and_then(|notation| notation.to_record(&id)) {
+        Ok(record) => record,
+        Err(error) => return HttpResponse::BadRequest().json(ErrorResponse::new(error)),
+    };
+    match web::block(move || history.record(&record).map(|()| record)).await {
+        Ok(Ok(record)) => HttpResponse::Created().json(record),
+        Ok(Err(error)) => HttpResponse::InternalServerError().json(ErrorResponse::new(error)),
+        Err(error) => HttpResponse::InternalServerError().json(ErrorResponse::new(error)),
+    }
+}
+
+// Züge eines laufenden Spiels gegen die Engine oder zwischen zwei Menschen oder eines Spiels aus dem Verlauf
+async fn game_moves(
+    store: &SessionStore,
+    matches: &MatchStore,
+    history: Option<web::Data<GameHistory>>,
+    id: &str,
+) -> Result<Vec<u8>, HttpResponse> {
+    let error = match store.game(id) {
+        Ok(game) => return Ok(game.moves),
+        Err(error) => error,
+    };
+    if let Ok(game) = matches.get(id) {
+        return Ok(game.moves);
+    }
+    let Some(history) = history else {
+        return Err(error_response(error));
+    };
+    let search_id = id.to_string();
+    match web::block(move || history.get(&search_id)).await {
+        Ok(Ok(Some(record))) => Ok(record
+            .moves
+            .iter()
+            .map(|recorded| recorded.column)
+            .collect()),
+        Ok(Ok(None)) => Err(error_response(error)),
+        Ok(Err(error)) => Err(HttpResponse::InternalServerError().json(ErrorResponse::new(error))),
+        Err(error) => Err(HttpResponse::InternalServerError().json(ErrorResponse::new(error))),
+    }
+}
+
+/*
+   Nachbesprechung: bewertet jeden Zug des Spiels mit der Engine und ordnet ihn als best, good, inaccuracy,
+   mistake oder blunder ein. Nach dem eigenen Timeout für Nachbesprechungen (review_timeout) wird die
+   Nachbesprechung bis zum zuletzt bewerteten Zug geliefert
+*/
+#[post("/review")]
+// jeder Parameter ist ein eigener Extraktor von actix
+#[allow(clippy::too_many_arguments)]
+async fn post_review(
+    store: web::Data<SessionStore>,
+    matches: web::Data<MatchStore>,
+    history: Option<web::Data<GameHistory>>,
+    pool: web::Data<ComputePool>,
+    search_options: web::Data<SearchOptions>,
+    registry: web::Data<SearchRegistry>,
+    config: web::Data<Config>,
+    info: Json<ReviewInfo>,
+    request_id: RequestId,
+) -> impl Responder {
+    let info = info.into_inner();
+    let difficulty = match Difficulty::from_level(info.level.unwrap_or(DEFAULT_REVIEW_LEVEL)) {
+        Ok(difficulty) => difficulty,
+        Err(error) => return HttpResponse::BadRequest().json(ErrorResponse::new(error)),
+    };
+    let moves = match (info.moves, &info.game_id) {
+        (Some(moves), None) => moves,
+        (None, Some(id)) => match game_moves(&store, &matches, history, id).await {
+            Ok(moves) => moves,
+            Err(response) => return response,
+        },
+        _ => {
+            return HttpResponse::BadRequest()
+                .json(ErrorResponse::new("expected either moves or game_id"))
+        }
+    };
+
+    let cancel = CancelToken::new();
+    // beim Herunterfahren wird auch die Nachbesprechung abgebrochen
+    let _guard = CancelGuard::tracked(cancel.clone(), registry.into_inner());
+    let search_options = SearchOptions {
+        cancel: cancel.clone(),
+        request_id: Some(request_id.0),
+        game_id: info.game_id,
+        ..search_options.as_ref().clone()
+    };
+    let reviewed = pool
+        .run_cancellable_for(
+            config.review_timeout,
+            move || review_game(&moves, &difficulty, &search_options),
+            || cancel.cancel(),
+        )
+        .await;
+    match reviewed {
+        Ok(Ok(review)) => HttpResponse::Ok().json(review),
+        Ok(Err(error)) => HttpResponse::BadRequest().json(ErrorResponse::new(error)),
+        Err(error) => pool_error_response(error),
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(create_game)
+        .service(get_game)
+        .service(play_move)
+        .service(undo_move)
+        .service(remove_game)
+        .service(get_player)
+        .service(list_history)
+        .service(get_history)
+        .service(get_record)
+        .service(import_record)
+        .service(post_review);
+}
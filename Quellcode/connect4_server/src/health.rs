@@ -0,0 +1,223 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Serialize;
+
+use crate::connect4ai::{
+    next_move_with, Difficulty, GameBoard, NextMoveResult, SearchOptions, COMPUTER_PLAYER,
+    USER_PLAYER,
+};
+use crate::ladder::MAX_LEVEL;
+use crate::pool::PoolStatus;
+
+// so lange gilt ein Selbsttest der Engine, bevor /readyz ihn erneut ausführt
+pub const SELF_TEST_INTERVAL: Duration = Duration::from_secs(60);
+// Spalte, mit der die Engine im Selbsttest gewinnen muss
+const SELF_TEST_WINNING_COLUMN: u8 = 3;
+
+// Ergebnis des Selbsttests der Engine
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EngineCheck {
+    pub ok: bool,
+    pub column: Option<u8>,
+    pub elapsed_ms: u64,
+    // Alter des zwischengespeicherten Ergebnisses
+    pub age_s: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/*
+   Zustand des Rechen-Pools. Warteschlange und beschäftigte Threads gehören zum normalen Betrieb, nur ein Pool
+   ohne Threads oder einer, in dem seit langem keine Aufgabe fertig geworden ist, gilt als nicht bereit
+*/
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PoolCheck {
+    pub ok: bool,
+    pub workers: usize,
+    pub busy: usize,
+}
+
+impl PoolCheck {
+    pub fn new(status: &PoolStatus) -> PoolCheck {
+        PoolCheck {
+            ok: status.is_live(),
+            workers: status.workers,
+            busy: status.busy,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub profile: &'static str,
+    pub target: String,
+    pub features: Vec<&'static str>,
+}
+
+impl BuildInfo {
+    pub fn current() -> BuildInfo {
+        let mut features = Vec::new();
+        if cfg!(feature = "tls") {
+            features.push("tls");
+        }
+        BuildInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            profile: if cfg!(debug_assertions) {
+                "debug"
+            } else {
+                "release"
+            },
+            target: format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS),
+            features,
+        }
+    }
+}
+
+// Antwort von /readyz, ready nur wenn alle Prüfungen erfolgreich waren
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub uptime_s: u64,
+    pub build: BuildInfo,
+    pub engine: EngineCheck,
+    pub pool: PoolCheck,
+}
+
+impl Readiness {
+    pub fn new(uptime: Duration, engine: EngineCheck, pool: PoolCheck) -> Readiness {
+        Readiness {
+            ready: engine.ok && pool.ok,
+            uptime_s: uptime.as_secs(),
+            build: BuildInfo::current(),
+            engine,
+            pool,
+        }
+    }
+}
+
+/*
+   Selbsttest der Engine: in einer Stellung mit drei Steinen des Computers in der untersten Reihe
+   muss die stärkste Stufe den Gewinnzug finden. Ohne Zeitlimit ist die Suche nach wenigen Millisekunden beendet
+*/
+pub fn engine_self_test() -> EngineCheck {
+    let mut game_board = GameBoard::new();
+    for column in 0..3 {
+        game_board.drop_chip(column, COMPUTER_PLAYER);
+        game_board.drop_chip(column, USER_PLAYER);
+    }
+
+    let start = Instant::now();
+    let difficulty = Difficulty::from_level(MAX_LEVEL)
+        .expect("MAX_LEVEL is a valid level")
+        .fixed_depth();
+    let (field, _, result, _) = next_move_with(
+        &mut game_board,
+        true,
+        &difficulty,
+        0,
+        &mut StdRng::seed_from_u64(0),
+        &SearchOptions::default(),
+    );
+    let column = field.map(|field| field.x);
+    let error = match (column, result) {
+        (Some(SELF_TEST_WINNING_COLUMN), NextMoveResult::ComputerWins) => None,
+        (column, result) => Some(format!(
+            "expected winning move in column {SELF_TEST_WINNING_COLUMN}, engine played {column:?} ({result:?})"
+        )),
+    };
+    EngineCheck {
+        ok: error.is_none(),
+        column,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+        age_s: 0,
+        error,
+    }
+}
+
+/*
+   Zustand für /healthz und /readyz. Das Ergebnis des Selbsttests wird für SELF_TEST_INTERVAL
+   zwischengespeichert, damit häufige Abfragen des Orchestrators keine Rechenzeit kosten
+    - running: es läuft höchstens ein Selbsttest, gleichzeitige Abfragen warten auf dessen Ergebnis
+*/
+pub struct Health {
+    started: Instant,
+    self_test: Mutex<Option<(Instant, EngineCheck)>>,
+    running: tokio::sync::Mutex<()>,
+}
+
+impl Health {
+    pub fn new() -> Health {
+        Health {
+            started: Instant::now(),
+            self_test: Mutex::new(None),
+            running: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /*
+       Zwischengespeichertes Ergebnis oder ein neuer Selbsttest über run. Schlägt run selbst fehl (z.B. weil kein
+       Thread frei ist), wird der Fehler gemeldet, aber nicht zwischengespeichert
+    */
+    pub async fn self_test<F, R>(&self, run: F) -> EngineCheck
+    where
+        F: FnOnce() -> R,
+        R: Future<Output = Result<EngineCheck, String>>,
+    {
+        if let Some(engine_check) = self.cached_self_test(Instant::now()) {
+            return engine_check;
+        }
+        let _running = self.running.lock().await;
+        // ein gleichzeitig laufender Selbsttest hat das Ergebnis eventuell schon gespeichert
+        if let Some(engine_check) = self.cached_self_test(Instant::now()) {
+            return engine_check;
+        }
+        match run().await {
+            Ok(engine_check) => {
+                self.store_self_test(Instant::now(), engine_check.clone());
+                engine_check
+            }
+            Err(error) => EngineCheck {
+                ok: false,
+                column: None,
+                elapsed_ms: 0,
+                age_s: 0,
+                error: Some(error),
+            },
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    // zwischengespeichertes Ergebnis mit aktualisiertem Alter, None wenn der Test erneut laufen muss
+    pub fn cached_self_test(&self, now: Instant) -> Option<EngineCheck> {
+        let self_test = self.self_test.lock().unwrap();
+        let (checked, engine_check) = self_test.as_ref()?;
+        let age = now.saturating_duration_since(*checked);
+        if age >= SELF_TEST_INTERVAL {
+            return None;
+        }
+        Some(EngineCheck {
+            age_s: age.as_secs(),
+            ..engine_check.clone()
+        })
+    }
+
+    pub fn store_self_test(&self, checked: Instant, engine_check: EngineCheck) {
+        *self.self_test.lock().unwrap() = Some((checked, engine_check));
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Health::new()
+    }
+}
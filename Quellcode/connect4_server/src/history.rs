@@ -0,0 +1,354 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use serde::{Deserialize, Serialize};
+
+use crate::connect4ai::NextMoveResult;
+use crate::session::Game;
+
+pub const DEFAULT_LIST_LIMIT: usize = 50;
+pub const MAX_LIST_LIMIT: usize = 500;
+
+/*
+   Schema der Datenbank, jede Migration wird genau einmal in dieser Reihenfolge ausgeführt.
+   Die Anzahl der ausgeführten Migrationen steht in PRAGMA user_version.
+   Bestehende Migrationen dürfen nicht mehr geändert werden, Änderungen kommen als neue Migration dazu
+*/
+const MIGRATIONS: [&str; 2] = [
+    "
+    CREATE TABLE games (
+        id TEXT PRIMARY KEY,
+        player TEXT NOT NULL,
+        opponent TEXT,
+        level INTEGER,
+        adaptive INTEGER NOT NULL,
+        computer_started INTEGER NOT NULL,
+        seed INTEGER NOT NULL,
+        result TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        finished_at INTEGER NOT NULL
+    );
+    CREATE INDEX games_player ON games (player, finished_at);
+    CREATE INDEX games_opponent ON games (opponent, finished_at);
+    CREATE TABLE moves (
+        game_id TEXT NOT NULL REFERENCES games (id) ON DELETE CASCADE,
+        ply INTEGER NOT NULL,
+        column_index INTEGER NOT NULL,
+        score INTEGER,
+        PRIMARY KEY (game_id, ply)
+    );
+",
+    "ALTER TABLE games ADD COLUMN takebacks INTEGER NOT NULL DEFAULT 0;",
+];
+
+#[derive(Debug, PartialEq)]
+pub enum HistoryError {
+    Database(String),
+    // die Datenbank wurde von einer neueren Version des Servers angelegt
+    UnknownSchema(usize),
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryError::Database(error) => write!(f, "game history database error: {error}"),
+            HistoryError::UnknownSchema(version) => write!(
+                f,
+                "game history schema version {version} is newer than this server ({})",
+                MIGRATIONS.len()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+impl From<rusqlite::Error> for HistoryError {
+    fn from(error: rusqlite::Error) -> Self {
+        HistoryError::Database(error.to_string())
+    }
+}
+
+/*
+   Zug eines gespeicherten Spiels
+    - score: Score der Engine nach ihrem Zug, None bei Zügen eines Menschen
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedMove {
+    pub column: u8,
+    pub score: Option<i64>,
+}
+
+/*
+   Ein beendetes Spiel, wie es in der Datenbank steht
+    - opponent: Name des Gegners, None wenn die Engine gespielt hat
+    - level: Stufe der Engine, None bei Spielen zwischen zwei Menschen
+    - result: "player_wins", "opponent_wins" (bzw. die Engine) oder "draw" aus Sicht von player
+    - started_at / finished_at: Unix-Zeit in Sekunden
+    - takebacks: Anzahl der zurückgenommenen Züge, solche Spiele zählen nur eingeschränkt für das Rating
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub id: String,
+    pub player: String,
+    pub opponent: Option<String>,
+    pub level: Option<u8>,
+    pub adaptive: bool,
+    pub computer_started: bool,
+    pub seed: u64,
+    pub result: String,
+    pub started_at: u64,
+    pub finished_at: u64,
+    #[serde(default)]
+    pub takebacks: u8,
+    pub moves: Vec<RecordedMove>,
+}
+
+impl GameRecord {
+    // None, solange das Spiel nicht beendet ist
+    pub fn from_game(game: &Game) -> Option<GameRecord> {
+        let result = match game.result {
+            NextMoveResult::PlayerWins => "player_wins",
+            NextMoveResult::ComputerWins => "opponent_wins",
+            NextMoveResult::Draw => "draw",
+            NextMoveResult::NextMove | NextMoveResult::None => return None,
+        };
+        Some(GameRecord {
+            id: game.id.clone(),
+            player: game.player.clone(),
+            opponent: None,
+            level: Some(game.level),
+            adaptive: game.adaptive,
+            computer_started: game.computer_started,
+            seed: game.seed,
+            result: result.to_string(),
+            started_at: game.started_at,
+            finished_at: game.finished_at?,
+            takebacks: game.takebacks,
+            moves: game
+                .moves
+                .iter()
+                .enumerate()
+                .map(|(ply, &column)| RecordedMove {
+                    column,
+                    score: game.scores.get(ply).copied().flatten(),
+                })
+                .collect(),
+        })
+    }
+}
+
+// Eintrag der Spieleliste, ohne die einzelnen Züge
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GameSummary {
+    pub id: String,
+    pub player: String,
+    pub opponent: Option<String>,
+    pub level: Option<u8>,
+    pub computer_started: bool,
+    pub result: String,
+    pub moves: usize,
+    pub takebacks: u8,
+    pub started_at: u64,
+    pub finished_at: u64,
+}
+
+/*
+   Filter der Spieleliste, neueste Spiele zuerst
+    - player: nur Spiele, an denen der Spieler beteiligt war
+    - limit: höchstens MAX_LIST_LIMIT Spiele
+*/
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct HistoryQuery {
+    pub player: Option<String>,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+/*
+   Verlauf aller beendeten Spiele in einer SQLite-Datenbank. Kann auch ohne Server geöffnet werden,
+   z.B. um gespeicherte Spiele auszuwerten
+*/
+pub struct GameHistory {
+    connection: Mutex<Connection>,
+}
+
+impl GameHistory {
+    // öffnet bzw. erzeugt die Datenbank und bringt ihr Schema auf den aktuellen Stand
+    pub fn open(path: &Path) -> Result<GameHistory, HistoryError> {
+        GameHistory::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<GameHistory, HistoryError> {
+        GameHistory::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut connection: Connection) -> Result<GameHistory, HistoryError> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut connection)?;
+        Ok(GameHistory {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    pub fn schema_version(&self) -> Result<usize, HistoryError> {
+        schema_version(&self.connection.lock().unwrap())
+    }
+
+    // speichert ein Spiel, ein bereits gespeichertes Spiel mit derselben Id wird ersetzt
+    pub fn record(&self, record: &GameRecord) -> Result<(), HistoryError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        transaction.execute("DELETE FROM games WHERE id = ?1", params![record.id])?;
+        transaction.execute(
+            "INSERT INTO games (id, player, opponent, level, adaptive, computer_started, seed, result,
+                started_at, finished_at, takebacks)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                record.id,
+                record.player,
+                record.opponent,
+                record.level,
+                record.adaptive,
+                record.computer_started,
+                // SQLite kennt nur vorzeichenbehaftete Zahlen, das Bitmuster des Seeds bleibt erhalten
+                record.seed as i64,
+                record.result,
+                record.started_at as i64,
+                record.finished_at as i64,
+                record.takebacks,
+            ],
+        )?;
+        insert_moves(&transaction, record)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    // entfernt ein Spiel samt Zügen, z.B. wenn es nach einer Zugrücknahme weiterläuft
+    pub fn remove(&self, id: &str) -> Result<(), HistoryError> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM games WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<GameRecord>, HistoryError> {
+        let connection = self.connection.lock().unwrap();
+        let record = connection
+            .query_row(
+                "SELECT id, player, opponent, level, adaptive, computer_started, seed, result,
+                    started_at, finished_at, takebacks
+                 FROM games WHERE id = ?1",
+                params![id],
+                record_from_row,
+            )
+            .optional()?;
+        let Some(mut record) = record else {
+            return Ok(None);
+        };
+
+        let mut statement = connection
+            .prepare("SELECT column_index, score FROM moves WHERE game_id = ?1 ORDER BY ply")?;
+        record.moves = statement
+            .query_map(params![id], |row| {
+                Ok(RecordedMove {
+                    column: row.get(0)?,
+                    score: row.get(1)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(Some(record))
+    }
+
+    pub fn list(&self, query: &HistoryQuery) -> Result<Vec<GameSummary>, HistoryError> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_LIST_LIMIT)
+            .min(MAX_LIST_LIMIT);
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT id, player, opponent, level, computer_started, result, started_at, finished_at,
+                (SELECT COUNT(*) FROM moves WHERE game_id = games.id), takebacks
+             FROM games
+             WHERE ?1 IS NULL OR player = ?1 OR opponent = ?1
+             ORDER BY finished_at DESC, id
+             LIMIT ?2 OFFSET ?3",
+        )?;
+        let summaries = statement
+            .query_map(
+                params![query.player, limit as i64, query.offset as i64],
+                |row| {
+                    Ok(GameSummary {
+                        id: row.get(0)?,
+                        player: row.get(1)?,
+                        opponent: row.get(2)?,
+                        level: row.get(3)?,
+                        computer_started: row.get(4)?,
+                        result: row.get(5)?,
+                        started_at: row.get::<_, i64>(6)? as u64,
+                        finished_at: row.get::<_, i64>(7)? as u64,
+                        moves: row.get::<_, i64>(8)? as usize,
+                        takebacks: row.get(9)?,
+                    })
+                },
+            )?
+            .collect::<Result<_, _>>()?;
+        Ok(summaries)
+    }
+}
+
+fn schema_version(connection: &Connection) -> Result<usize, HistoryError> {
+    let version: i64 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    Ok(version as usize)
+}
+
+fn migrate(connection: &mut Connection) -> Result<(), HistoryError> {
+    let version = schema_version(connection)?;
+    if version > MIGRATIONS.len() {
+        return Err(HistoryError::UnknownSchema(version));
+    }
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index as i64 + 1)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+fn insert_moves(transaction: &Transaction, record: &GameRecord) -> Result<(), HistoryError> {
+    let mut statement = transaction
+        .prepare("INSERT INTO moves (game_id, ply, column_index, score) VALUES (?1, ?2, ?3, ?4)")?;
+    for (ply, recorded) in record.moves.iter().enumerate() {
+        statement.execute(params![
+            record.id,
+            ply as i64,
+            recorded.column,
+            recorded.score
+        ])?;
+    }
+    Ok(())
+}
+
+fn record_from_row(row: &Row) -> rusqlite::Result<GameRecord> {
+    Ok(GameRecord {
+        id: row.get(0)?,
+        player: row.get(1)?,
+        opponent: row.get(2)?,
+        level: row.get(3)?,
+        adaptive: row.get(4)?,
+        computer_started: row.get(5)?,
+        seed: row.get::<_, i64>(6)? as u64,
+        result: row.get(7)?,
+        started_at: row.get::<_, i64>(8)? as u64,
+        finished_at: row.get::<_, i64>(9)? as u64,
+        takebacks: row.get(10)?,
+        moves: Vec::new(),
+    })
+}
@@ -0,0 +1,346 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::Serialize;
+
+use crate::connect4ai::{
+    available_fields, check_for_row, next_move, Difficulty, GameBoard, COMPUTER_PLAYER, USER_PLAYER,
+};
+use crate::skill::Skill;
+
+pub const MIN_LEVEL: u8 = 1;
+pub const MAX_LEVEL: u8 = LEVELS.len() as u8;
+
+// Stufen, auf die die bisherigen Schwierigkeiten Easy, Medium und Hard abgebildet werden
+pub const EASY_LEVEL: u8 = 4;
+pub const MEDIUM_LEVEL: u8 = 6;
+pub const HARD_LEVEL: u8 = 9;
+
+// Rating der schwächsten Stufe, an dem die übrigen Ratings ausgerichtet werden
+const BASE_RATING: f64 = 600.0;
+
+/*
+   Eine Stufe der Schwierigkeitsleiter
+    - depth: Suchtiefe, die unabhängig von der Hardware immer erreicht wird
+    - time_limit: Bedenkzeit, in der die stärksten Stufen über depth hinaus tiefer suchen, None bei fester Tiefe
+    - zugzwang_evaluation: ob Zugzwänge in die Bewertung einfließen
+    - skill: menschenähnliche Schwächen der Stufe
+    - rating: per Selbstspiel gemessene Spielstärke (siehe calibrate)
+    - rating_estimated: das Rating wurde ohne die Bedenkzeit gemessen, die Stufe spielt stärker als angegeben
+    - takebacks: Anzahl der Züge, die ein Spieler pro Spiel zurücknehmen darf (siehe TakebackLimits)
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Level {
+    pub level: u8,
+    pub depth: u8,
+    #[serde(rename = "time_limit_ms", serialize_with = "serialize_millis")]
+    pub time_limit: Option<Duration>,
+    pub zugzwang_evaluation: bool,
+    #[serde(skip)]
+    pub skill: Skill,
+    pub rating: u16,
+    pub rating_estimated: bool,
+    pub takebacks: u8,
+}
+
+const fn level(
+    level: u8,
+    depth: u8,
+    time_limit_ms: Option<u64>,
+    zugzwang_evaluation: bool,
+    skill: Skill,
+    rating: u16,
+    takebacks: u8,
+) -> Level {
+    Level {
+        level,
+        depth,
+        time_limit: match time_limit_ms {
+            Some(time_limit_ms) => Some(Duration::from_millis(time_limit_ms)),
+            None => None,
+        },
+        zugzwang_evaluation,
+        skill,
+        rating,
+        rating_estimated: time_limit_ms.is_some(),
+        takebacks,
+    }
+}
+
+/*
+   Ratings wurden mit `cargo test --release -- --ignored calibrate_ladder --nocapture` ermittelt. Dabei
+   rechnen alle Stufen mit fester Tiefe, mit Bedenkzeit spielen die Stufen 9 und 10 also eher stärker und ihr
+   Rating ist als Schätzung markiert
+*/
+pub const LEVELS: [Level; 10] = [
+    level(1, 1, None, false, Skill::new(8.0, 0.6, 8), 600, 5),
+    level(2, 2, None, false, Skill::new(5.0, 0.45, 6), 748, 5),
+    level(3, 3, None, false, Skill::new(4.0, 0.35, 4), 831, 3),
+    level(4, 4, None, false, Skill::new(3.0, 0.25, 3), 931, 3),
+    level(5, 5, None, true, Skill::new(1.2, 0.12, 2), 1021, 2),
+    level(6, 6, None, true, Skill::new(1.0, 0.1, 1), 1104, 2),
+    level(7, 6, None, true, Skill::new(0.3, 0.05, 1), 1197, 1),
+    level(8, 6, None, true, Skill::new(0.1, 0.0, 0), 1403, 1),
+    level(9, 8, Some(2000), true, Skill::PERFECT, 1664, 0),
+    level(10, 9, Some(3000), true, Skill::PERFECT, 1803, 0),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DifficultyError {
+    UnknownLevel(u8),
+    UnknownDifficulty(u8),
+}
+
+impl fmt::Display for DifficultyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DifficultyError::UnknownLevel(level) => write!(
+                f,
+                "unknown level {level}, expected {MIN_LEVEL} to {MAX_LEVEL}"
+            ),
+            DifficultyError::UnknownDifficulty(difficulty) => write!(
+                f,
+                "unknown difficulty {difficulty}, expected 0 (easy), 1 (medium) or 2 (hard)"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DifficultyError {}
+
+pub fn get_level(level: u8) -> Result<&'static Level, DifficultyError> {
+    LEVELS
+        .iter()
+        .find(|l| l.level == level)
+        .ok_or(DifficultyError::UnknownLevel(level))
+}
+
+/*
+   Bildet die bisherigen drei Schwierigkeiten (0: Easy, 1: Medium, 2: Hard) auf die Leiter ab. Gespielt wird mit
+   Difficulty::from_int bzw. fixed_depth, Hard rechnet also wie früher genau bis Tiefe 8
+*/
+pub fn level_from_difficulty(difficulty: u8) -> Result<u8, DifficultyError> {
+    match difficulty {
+        0 => Ok(EASY_LEVEL),
+        1 => Ok(MEDIUM_LEVEL),
+        2 => Ok(HARD_LEVEL),
+        _ => Err(DifficultyError::UnknownDifficulty(difficulty)),
+    }
+}
+
+impl Level {
+    pub fn difficulty(&self) -> Difficulty {
+        Difficulty {
+            level: Some(self.level),
+            calculation_depth: self.depth,
+            time_limit: self.time_limit,
+            zugzwang_evaluation: self.zugzwang_evaluation,
+            skill: self.skill,
+        }
+    }
+}
+
+/*
+   Abweichende Anzahl erlaubter Zugrücknahmen je Stufe, konfiguriert als "Stufe=Anzahl" (z.B. 9=1).
+   Für Stufen ohne Eintrag gilt der Wert der Schwierigkeitsleiter
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TakebackLimits(BTreeMap<u8, u8>);
+
+impl TakebackLimits {
+    pub fn parse(entries: &[String]) -> Result<TakebackLimits, String> {
+        let mut limits = BTreeMap::new();
+        for entry in entries {
+            let invalid = || format!("takebacks: invalid entry '{entry}', expected LEVEL=COUNT");
+            let (level, count) = entry.split_once('=').ok_or_else(invalid)?;
+            let level = level.trim().parse().map_err(|_| invalid())?;
+            let count = count.trim().parse().map_err(|_| invalid())?;
+            get_level(level).map_err(|error| format!("takebacks: {error}"))?;
+            limits.insert(level, count);
+        }
+        Ok(TakebackLimits(limits))
+    }
+
+    pub fn limit(&self, level: u8) -> u8 {
+        match self.0.get(&level) {
+            Some(limit) => *limit,
+            None => get_level(level).map_or(0, |level| level.takebacks),
+        }
+    }
+
+    // alle Stufen der Schwierigkeitsleiter mit den Zugrücknahmen, die tatsächlich gelten
+    pub fn levels(&self) -> Vec<Level> {
+        LEVELS
+            .iter()
+            .map(|level| Level {
+                takebacks: self.limit(level.level),
+                ..*level
+            })
+            .collect()
+    }
+}
+
+fn serialize_millis<S: serde::Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serializer.serialize_some(&(duration.as_millis() as u64)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/*
+   ------------ SELBSTSPIEL ------------
+*/
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GameOutcome {
+    FirstWins,
+    SecondWins,
+    Draw,
+}
+
+/*
+   Lässt zwei Stufen gegeneinander spielen. Die erste Stufe beginnt.
+   Die ersten opening_moves Züge werden zufällig gespielt, damit sich die Partien unterscheiden.
+*/
+pub fn self_play_game<R: Rng>(
+    first: &Difficulty,
+    second: &Difficulty,
+    opening_moves: u8,
+    rng: &mut R,
+) -> GameOutcome {
+    // der beginnende Spieler hat die Steine des Computers, der zweite die des Nutzers
+    let mut game_board = GameBoard::new();
+    let mut blunders = [0u8; 2];
+    let mut moves_played = 0;
+
+    loop {
+        let side = moves_played % 2;
+        let stone = if side == 0 {
+            COMPUTER_PLAYER
+        } else {
+            USER_PLAYER
+        };
+
+        let free_fields = available_fields(&game_board);
+        if free_fields.is_empty() {
+            return GameOutcome::Draw;
+        }
+
+        let field = if moves_played < opening_moves as usize {
+            *free_fields.choose(rng).unwrap()
+        } else {
+            // die Engine spielt immer als Computer, daher wird das Brett für den zweiten Spieler gespiegelt
+            let mut view = if side == 0 {
+                game_board.clone()
+            } else {
+                game_board.swap_players()
+            };
+            let difficulty = if side == 0 { first } else { second };
+            let result = next_move(&mut view, side == 0, difficulty, blunders[side], rng);
+            if result.3 {
+                blunders[side] += 1;
+            }
+            match result.0 {
+                Some(field) => field,
+                None => return GameOutcome::Draw,
+            }
+        };
+
+        game_board.set(field.x as usize, field.y as usize, stone);
+        if check_for_row(&game_board.grid, stone, 4).0 {
+            return if side == 0 {
+                GameOutcome::FirstWins
+            } else {
+                GameOutcome::SecondWins
+            };
+        }
+        moves_played += 1;
+    }
+}
+
+/*
+   Misst die Spielstärke aller Stufen per Selbstspiel:
+   jede Stufe spielt games_per_pair Partien gegen die nächsten beiden Stufen (abwechselnd beginnend),
+   aus den Ergebnissen werden Elo-Ratings geschätzt
+*/
+pub fn calibrate<R: Rng>(games_per_pair: usize, rng: &mut R) -> Vec<(u8, u16)> {
+    // ohne Bedenkzeit hängen die Ergebnisse nur vom Zufallsgenerator ab und sind reproduzierbar
+    let difficulties: Vec<Difficulty> = LEVELS
+        .iter()
+        .map(|level| level.difficulty().fixed_depth())
+        .collect();
+    let mut results: Vec<(usize, usize, f64)> = Vec::new(); // (Stufe a, Stufe b, Punkte von a)
+
+    for a in 0..LEVELS.len() {
+        for b in (a + 1)..(a + 3).min(LEVELS.len()) {
+            for game in 0..games_per_pair {
+                let score = if game % 2 == 0 {
+                    match self_play_game(&difficulties[a], &difficulties[b], 2, rng) {
+                        GameOutcome::FirstWins => 1.0,
+                        GameOutcome::SecondWins => 0.0,
+                        GameOutcome::Draw => 0.5,
+                    }
+                } else {
+                    match self_play_game(&difficulties[b], &difficulties[a], 2, rng) {
+                        GameOutcome::FirstWins => 0.0,
+                        GameOutcome::SecondWins => 1.0,
+                        GameOutcome::Draw => 0.5,
+                    }
+                };
+                results.push((a, b, score));
+            }
+        }
+    }
+
+    estimate_ratings(LEVELS.len(), &results)
+        .into_iter()
+        .enumerate()
+        .map(|(i, rating)| (LEVELS[i].level, rating.round().max(0.0) as u16))
+        .collect()
+}
+
+/*
+   Schätzt Elo-Ratings aus Einzelergebnissen (Spieler a, Spieler b, Punkte von a)
+   Jede Paarung erhält ein zusätzliches virtuelles Remis, damit 100% Siegquoten endlich bleiben
+*/
+pub fn estimate_ratings(players: usize, results: &[(usize, usize, f64)]) -> Vec<f64> {
+    let mut pairings: Vec<(usize, usize, f64, f64)> = Vec::new(); // (a, b, Punkte von a, Partien)
+    for (a, b, score) in results {
+        match pairings.iter_mut().find(|p| p.0 == *a && p.1 == *b) {
+            Some(pairing) => {
+                pairing.2 += score;
+                pairing.3 += 1.0;
+            }
+            None => pairings.push((*a, *b, 0.5 + score, 2.0)),
+        }
+    }
+
+    let mut ratings = vec![0.0; players];
+    for _ in 0..10000 {
+        let mut gradient = vec![0.0; players];
+        let mut games = vec![0.0; players];
+        for (a, b, score, count) in pairings.iter() {
+            let expected = count / (1.0 + 10f64.powf((ratings[*b] - ratings[*a]) / 400.0));
+            gradient[*a] += score - expected;
+            gradient[*b] -= score - expected;
+            games[*a] += count;
+            games[*b] += count;
+        }
+        for i in 0..players {
+            if games[i] > 0.0 {
+                ratings[i] += 200.0 * gradient[i] / games[i];
+            }
+        }
+    }
+
+    let offset = BASE_RATING - ratings[0];
+    ratings.iter().map(|rating| rating + offset).collect()
+}
@@ -0,0 +1,179 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::future::{ready, Ready};
+use std::io::Write;
+use std::time::Duration;
+
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use clap::ValueEnum;
+use log::kv::{Error, Key, Value, VisitSource};
+use log::Record;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::connect4ai::SearchReport;
+
+// Header, über den Clients und Proxys eine Id der Anfrage mitgeben können, die Antwort enthält sie immer
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+// Ziele der strukturierten Logzeilen, damit sie getrennt gefiltert werden können (z.B. "connect4_server::search=info")
+pub const ACCESS_TARGET: &str = "connect4_server::access";
+pub const SEARCH_TARGET: &str = "connect4_server::search";
+
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+// Ausgabe der Logzeilen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    // lesbare Zeilen, Felder werden als key=value angehängt
+    #[default]
+    Text,
+    // ein JSON-Objekt pro Zeile, Felder sind eigene Schlüssel des Objekts
+    Json,
+}
+
+pub fn init(filters: &str, format: LogFormat) {
+    env_logger::Builder::new()
+        .parse_filters(filters)
+        .format(move |buf, record| {
+            let line = format_record(format, &buf.timestamp_millis().to_string(), record);
+            writeln!(buf, "{line}")
+        })
+        .init();
+}
+
+pub fn format_record(format: LogFormat, timestamp: &str, record: &Record) -> String {
+    let mut fields = Fields::default();
+    let _ = record.key_values().visit(&mut fields);
+
+    match format {
+        LogFormat::Text => {
+            let mut line = format!(
+                "[{timestamp} {:<5} {}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+            for (key, value) in fields.0 {
+                match value {
+                    JsonValue::String(value) if !value.contains([' ', '"', '=']) => {
+                        line.push_str(&format!(" {key}={value}"))
+                    }
+                    value => line.push_str(&format!(" {key}={value}")),
+                }
+            }
+            line
+        }
+        LogFormat::Json => {
+            // die Reihenfolge der Felder bleibt erhalten, daher wird das Objekt von Hand zusammengesetzt
+            let mut line = format!(
+                "{{\"timestamp\":{},\"level\":{},\"target\":{},\"message\":{}",
+                JsonValue::from(timestamp),
+                JsonValue::from(record.level().as_str()),
+                JsonValue::from(record.target()),
+                JsonValue::from(record.args().to_string())
+            );
+            for (key, value) in fields.0 {
+                line.push_str(&format!(",{}:{value}", JsonValue::from(key)));
+            }
+            line.push('}');
+            line
+        }
+    }
+}
+
+// Felder einer Logzeile in der Reihenfolge des Aufrufs, Felder ohne Wert (None) werden weggelassen
+#[derive(Default)]
+struct Fields(Vec<(String, JsonValue)>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        let value = serde_json::to_value(&value).unwrap_or_else(|_| value.to_string().into());
+        if !value.is_null() {
+            self.0.push((key.to_string(), value));
+        }
+        Ok(())
+    }
+}
+
+/*
+   Id einer Anfrage, mit der alle Logzeilen dieser Anfrage (auch die der Suche) zusammengefunden werden.
+   Wird von der Middleware in den Extensions der Anfrage abgelegt und kann in Handlern extrahiert werden
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    pub fn generate() -> RequestId {
+        RequestId(format!("{:016x}", rand::random::<u64>()))
+    }
+
+    // übernimmt die Id aus dem Header, wenn sie kurz ist und nur unverfängliche Zeichen enthält
+    pub fn from_header(header: Option<&str>) -> RequestId {
+        match header {
+            Some(id)
+                if !id.is_empty()
+                    && id.len() <= MAX_REQUEST_ID_LENGTH
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) =>
+            {
+                RequestId(id.to_string())
+            }
+            _ => RequestId::generate(),
+        }
+    }
+}
+
+impl FromRequest for RequestId {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<RequestId, actix_web::Error>>;
+
+    // ohne Middleware (z.B. in Tests) erhält die Anfrage eine neue Id
+    fn from_request(request: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let request_id = request.extensions().get::<RequestId>().cloned();
+        ready(Ok(request_id.unwrap_or_else(RequestId::generate)))
+    }
+}
+
+pub fn log_access(
+    request_id: &RequestId,
+    method: &str,
+    path: &str,
+    status: u16,
+    elapsed: Duration,
+    peer: Option<&str>,
+) {
+    log::info!(
+        target: ACCESS_TARGET,
+        request_id = request_id.0.as_str(),
+        method,
+        path,
+        status,
+        elapsed_ms = elapsed.as_millis() as u64,
+        peer;
+        "{method} {path} {status}"
+    );
+}
+
+// eine Zeile je Zug der Engine, über request_id bzw. game_id lassen sich alle Suchen einer Anfrage bzw. eines Spiels finden
+pub fn log_search(report: &SearchReport) {
+    log::info!(
+        target: SEARCH_TARGET,
+        request_id = report.request_id.as_deref(),
+        game_id = report.game_id.as_deref(),
+        position = report.position.as_str(),
+        level = report.level,
+        column = report.column,
+        score = report.score,
+        result:? = report.result,
+        blunder = report.blunder,
+        book = report.book,
+        cancelled = report.cancelled,
+        depth = report.depth,
+        nodes = report.nodes,
+        elapsed_ms = report.elapsed_ms;
+        "Suche beendet"
+    );
+}
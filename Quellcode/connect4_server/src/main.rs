@@ -1,21 +1,294 @@
-use std::{env, io};
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use actix_cors::Cors;
 use actix_web::web::Json;
-use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder, middleware};
-use serde::Deserialize;
-use log::debug;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Server, ServerHandle, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+#[cfg(feature = "tls")]
+use actix_web::http::StatusCode;
+use actix_web::middleware::{from_fn, Next};
+#[cfg(feature = "tls")]
+use actix_web::HttpRequest;
+use actix_web::{delete, get, post, web, App, HttpMessage, HttpResponse, HttpServer, Responder};
+use futures_util::future;
+use serde::{Deserialize, Serialize};
+use log::{debug, error, info, warn};
 
-use crate::connect4ai::{Difficulty, GameBoard, COMPUTER_PLAYER};
+use crate::access::{AccessControl, AccessError, API_KEY_HEADER};
+use crate::cancellation::{CancelGuard, CancelToken, SearchRegistry};
+use crate::config::Config;
+#[cfg(feature = "tls")]
+use crate::config::PlainHttp;
+use crate::connect4ai::{
+    moves_to_loss, moves_to_win, next_move_with, rng_from_seed, Difficulty, Field, GameBoard,
+    NextMoveResult, SearchOptions, SearchReport, COMPUTER_PLAYER,
+};
+use crate::health::{engine_self_test, Health, PoolCheck, Readiness};
+use crate::ladder::{level_from_difficulty, DifficultyError};
+use crate::logging::{RequestId, REQUEST_ID_HEADER};
+use crate::metrics::Metrics;
+use crate::multiplayer::MatchStore;
+use crate::pool::{ComputePool, PoolError};
+use crate::session::SessionStore;
+use crate::spectators::Spectators;
 
+mod access;
+mod book;
+mod cancellation;
+mod config;
 mod connect4ai;
+mod cors;
+mod games;
+mod health;
+mod history;
+mod ladder;
+mod logging;
+mod matches;
+mod metrics;
+mod multiplayer;
+mod notation;
+mod pool;
+mod rating;
+mod review;
+mod session;
+mod skill;
+mod spectators;
+mod stream;
+#[cfg(feature = "tls")]
+mod tls;
+mod transposition;
+mod watch;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+// Zeit nach dem Abbrechen der Suchen, in der actix noch auf deren Antworten wartet
+const SHUTDOWN_GRACE_S: u64 = 5;
 
 #[derive(Debug, Deserialize)]
 pub struct NextMoveInfo {
     computer_started: bool,
-    difficulty: u8,
+    // bisherige Schwierigkeit (0: Easy, 1: Medium, 2: Hard)
+    difficulty: Option<u8>,
+    // Stufe der Schwierigkeitsleiter, hat Vorrang vor difficulty
+    level: Option<u8>,
+    // bisherige Patzer des Computers in diesem Spiel, begrenzt durch das Patzer-Budget der Stufe
+    #[serde(default)]
+    blunders: u8,
+    /*
+       Seed für alle zufälligen Entscheidungen, ohne Angabe wird einer gewählt und mit der Antwort
+       zurückgegeben. Mit Seed rechnet die Engine mit einem Thread und ohne Bedenkzeit bis zur festen Tiefe
+       der Stufe, dieselbe Anfrage ergibt dann unabhängig von der Hardware denselben Zug
+    */
+    seed: Option<u64>,
+}
+
+impl NextMoveInfo {
+    // ohne Stufe und Schwierigkeit wird die konfigurierte Standardstufe gespielt
+    fn difficulty(&self, default_level: u8) -> Result<Difficulty, DifficultyError> {
+        let difficulty = match (self.level, self.difficulty) {
+            (Some(level), _) => Difficulty::from_level(level)?,
+            // die bisherigen Schwierigkeiten spielen wie früher mit fester Tiefe, auch Hard ohne Bedenkzeit
+            (None, Some(difficulty)) => {
+                Difficulty::from_level(level_from_difficulty(difficulty)?)?.fixed_depth()
+            }
+            (None, None) => Difficulty::from_level(default_level)?,
+        };
+        Ok(match self.seed {
+            Some(_) => difficulty.fixed_depth(),
+            None => difficulty,
+        })
+    }
+
+    // Threads der Suche, mit Seed nur einer, damit die Antwort reproduzierbar ist
+    fn threads(&self, configured: usize) -> usize {
+        match self.seed {
+            Some(_) => 1,
+            None => configured,
+        }
+    }
+
+    // Seed der Suche, damit auch Züge ohne vorgegebenen Seed nachgespielt werden können
+    fn seed(&self) -> u64 {
+        self.seed.unwrap_or_else(rand::random)
+    }
+}
+
+/*
+   Token und Guard für die Suche einer Anfrage: der Guard bricht die Suche ab, sobald actix die Anfrage verwirft.
+   Jede Suche ist in der Registry eingetragen, damit sie beim Herunterfahren abgebrochen werden kann. Mit einer
+   vom Server vergebenen search_id kann sie außerdem über DELETE /searches/{id} abgebrochen werden
+*/
+fn cancel_guard(
+    registry: web::Data<SearchRegistry>,
+    search_id: Option<&str>,
+) -> (CancelToken, CancelGuard) {
+    let cancel = CancelToken::new();
+    let guard = match search_id {
+        Some(search_id) => CancelGuard::registered(cancel.clone(), registry.into_inner(), search_id),
+        None => CancelGuard::tracked(cancel.clone(), registry.into_inner()),
+    };
+    (cancel, guard)
+}
+
+// Antwort eines Zugs: (Brett, Ergebnis, Score, Patzer, Niederlage in N Zügen, Sieg in N Zügen, Seed)
+type NextMoveResponse = (
+    GameBoard,
+    NextMoveResult,
+    i64,
+    bool,
+    Option<u8>,
+    Option<u8>,
+    u64,
+);
+
+fn next_move_response(
+    mut game_board: GameBoard,
+    result: (Option<Field>, i64, NextMoveResult, bool),
+    seed: u64,
+) -> NextMoveResponse {
+    let (field, score, next_move_result, blunder) = result;
+    if let Some(field) = field {
+        game_board.set(field.x as usize, field.y as usize, COMPUTER_PLAYER);
+    }
+    (
+        game_board,
+        next_move_result,
+        score,
+        blunder,
+        moves_to_loss(score),
+        moves_to_win(score),
+        seed,
+    )
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    error: String,
+}
+
+impl ErrorResponse {
+    fn new(error: impl ToString) -> ErrorResponse {
+        ErrorResponse {
+            error: error.to_string(),
+        }
+    }
+}
+
+// Antwort, wenn die Suche nicht im Rechen-Pool ausgeführt werden konnte
+pub fn pool_error_response(error: PoolError) -> HttpResponse {
+    let body = ErrorResponse::new(&error);
+    match error {
+        PoolError::Saturated | PoolError::Timeout(_) => HttpResponse::ServiceUnavailable()
+            .insert_header(("Retry-After", "1"))
+            .json(body),
+        PoolError::Closed => HttpResponse::InternalServerError().json(body),
+    }
+}
+
+// Antwort, wenn die Zugangskontrolle eine Anfrage ablehnt
+fn access_error_response(error: AccessError) -> HttpResponse {
+    let body = ErrorResponse::new(&error);
+    match error {
+        AccessError::MissingApiKey | AccessError::InvalidApiKey => HttpResponse::Unauthorized()
+            .insert_header(("WWW-Authenticate", API_KEY_HEADER))
+            .json(body),
+        AccessError::RateLimited(retry_after) => HttpResponse::TooManyRequests()
+            .insert_header(("Retry-After", retry_after.as_secs_f64().ceil().to_string()))
+            .json(body),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiKeyQuery {
+    api_key: Option<String>,
+}
+
+// Browser können beim Aufbau eines WebSockets keine Header setzen, dort gilt der Schlüssel auch als ?api_key=
+fn websocket_api_key(request: &ServiceRequest) -> Option<String> {
+    let upgrade = request.headers().get(header::UPGRADE)?.to_str().ok()?;
+    if !upgrade.eq_ignore_ascii_case("websocket") {
+        return None;
+    }
+    web::Query::<ApiKeyQuery>::from_query(request.query_string())
+        .ok()?
+        .into_inner()
+        .api_key
+}
+
+// prüft API-Schlüssel und Begrenzung der Anfragen, bevor eine Anfrage ihren Handler erreicht
+async fn check_access(
+    access: web::Data<AccessControl>,
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let query_key = websocket_api_key(&request);
+    let api_key = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|api_key| api_key.to_str().ok())
+        .or(query_key.as_deref());
+    let ip = request.peer_addr().map(|address| address.ip().to_string());
+    if let Err(error) = access.check(
+        request.method().as_str(),
+        request.path(),
+        api_key,
+        ip.as_deref(),
+    ) {
+        let response = access_error_response(error);
+        return Ok(request.into_response(response).map_into_right_body());
+    }
+    next.call(request).await.map(ServiceResponse::map_into_left_body)
+}
+
+// misst Anzahl und Dauer aller Anfragen je Route
+async fn record_request(
+    metrics: web::Data<Metrics>,
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let response = next.call(request).await?;
+    metrics.record_request(&method, &route, response.status().as_u16(), start.elapsed());
+    Ok(response)
+}
+
+// vergibt jeder Anfrage eine Id, gibt sie im Header der Antwort zurück und protokolliert die Anfrage
+async fn log_request(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let start = Instant::now();
+    let request_id = RequestId::from_header(
+        request
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|request_id| request_id.to_str().ok()),
+    );
+    request.extensions_mut().insert(request_id.clone());
+    let method = request.method().to_string();
+    let path = request.path().to_string();
+    let peer = request.peer_addr().map(|address| address.ip().to_string());
+
+    let mut response = next.call(request).await?;
+    if let Ok(value) = HeaderValue::from_str(&request_id.0) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static("x-request-id"), value);
+    }
+    logging::log_access(
+        &request_id,
+        &method,
+        &path,
+        response.status().as_u16(),
+        start.elapsed(),
+        peer.as_deref(),
+    );
+    Ok(response)
 }
 
 #[get("/")]
@@ -29,44 +302,376 @@ async fn version() -> impl Responder {
     HttpResponse::Ok().body(VERSION.to_string())
 }
 
+// der Prozess lebt und beantwortet Anfragen, ohne die Engine zu prüfen
+#[get("/healthz")]
+async fn healthz(health: web::Data<Health>) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "ok",
+        "uptime_s": health.uptime().as_secs(),
+    }))
+}
+
+/*
+   Bereit für Anfragen, wenn der (zwischengespeicherte) Selbsttest der Engine erfolgreich war und die Threads des
+   Rechen-Pools arbeiten. Ein ausgelasteter Pool bleibt bereit, sonst würde die Instanz gerade unter Last aus der
+   Rotation genommen. Sonst 503, damit der Orchestrator keine Anfragen schickt
+*/
+#[get("/readyz")]
+async fn readyz(health: web::Data<Health>, pool: web::Data<ComputePool>) -> impl Responder {
+    // der Selbsttest läuft außerhalb des Rechen-Pools, damit er nicht hinter den Suchen der Spieler wartet
+    let engine = health
+        .self_test(|| async {
+            web::block(engine_self_test)
+                .await
+                .map_err(|error| error.to_string())
+        })
+        .await;
+    let pool = PoolCheck::new(&pool.status(Instant::now()));
+
+    let readiness = Readiness::new(health.uptime(), engine, pool);
+    if readiness.ready {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
+
+// Kennzahlen für Prometheus
+#[get("/metrics")]
+async fn prometheus_metrics(metrics: web::Data<Metrics>, store: web::Data<SessionStore>) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics.render(store.len(), store.pondering_games()))
+}
+
+// alle Stufen der Schwierigkeitsleiter mit ihrem ungefähren Rating und den konfigurierten Zugrücknahmen
+#[get("/levels")]
+async fn levels(config: web::Data<Config>) -> impl Responder {
+    HttpResponse::Ok().json(config.takebacks.levels())
+}
+
 #[post("next_move")]
-async fn next_move(game_board: Json<GameBoard>, info: web::Query<NextMoveInfo>) -> impl Responder {
-    let mut game_board = game_board.clone();
-    let result = connect4ai::next_move(
-        &mut game_board,
-        info.computer_started,
-        &Difficulty::from_int(info.difficulty),
-    );
-    let next_move = result.0;
-    let score = result.1;
-    let next_move_result = result.2;
-    if next_move.is_none() {
-        HttpResponse::Ok().json((game_board, next_move_result, score))
+async fn next_move(
+    game_board: Json<GameBoard>,
+    info: web::Query<NextMoveInfo>,
+    search_options: web::Data<SearchOptions>,
+    pool: web::Data<ComputePool>,
+    registry: web::Data<SearchRegistry>,
+    config: web::Data<Config>,
+    request_id: RequestId,
+) -> impl Responder {
+    let difficulty = match info.difficulty(config.default_level) {
+        Ok(difficulty) => difficulty,
+        Err(error) => return HttpResponse::BadRequest().json(ErrorResponse::new(error)),
+    };
+
+    let (cancel, _guard) = cancel_guard(registry, None);
+    let search_options = SearchOptions {
+        threads: info.threads(search_options.threads),
+        cancel: cancel.clone(),
+        request_id: Some(request_id.0),
+        ..search_options.as_ref().clone()
+    };
+
+    let mut game_board = game_board.into_inner();
+    let computer_started = info.computer_started;
+    let blunders = info.blunders;
+    let seed = info.seed();
+    // nach dem Timeout wird die Suche abgebrochen und der beste bisher gefundene Zug gespielt
+    let search = pool
+        .run_cancellable(
+            move || {
+                let result = next_move_with(
+                    &mut game_board,
+                    computer_started,
+                    &difficulty,
+                    blunders,
+                    &mut rng_from_seed(Some(seed)),
+                    &search_options,
+                );
+                (game_board, result)
+            },
+            || cancel.cancel(),
+        )
+        .await;
+    match search {
+        Ok((game_board, result)) => {
+            HttpResponse::Ok().json(next_move_response(game_board, result, seed))
+        }
+        Err(error) => pool_error_response(error),
+    }
+}
+
+/*
+   Bricht eine laufende Suche ab, sie antwortet dann mit dem besten bisher gefundenen Zug. Die Id ist entweder die
+   aus dem Ereignis "search" von /next_move/stream oder die Id eines Spiels, dessen Engine gerade antwortet
+*/
+#[delete("/searches/{id}")]
+async fn cancel_search(registry: web::Data<SearchRegistry>, id: web::Path<String>) -> impl Responder {
+    if registry.cancel(&id) {
+        HttpResponse::NoContent().finish()
     } else {
-        let next_move = next_move.unwrap();
-        game_board.set(next_move.x as usize, next_move.y as usize, COMPUTER_PLAYER);
-        HttpResponse::Ok().json((game_board, next_move_result, score))
+        HttpResponse::NotFound().json(ErrorResponse::new(format!("search {id} not found")))
+    }
+}
+
+// beantwortet Anfragen auf dem unverschlüsselten Port, wenn der Server HTTPS anbietet
+#[cfg(feature = "tls")]
+async fn plain_http(request: HttpRequest, mode: PlainHttp, https_port: u16) -> HttpResponse {
+    match mode {
+        PlainHttp::Redirect => {
+            let path_and_query = request
+                .uri()
+                .path_and_query()
+                .map_or("/", |path_and_query| path_and_query.as_str());
+            let location =
+                tls::https_location(request.connection_info().host(), https_port, path_and_query);
+            HttpResponse::PermanentRedirect()
+                .insert_header(("Location", location))
+                .finish()
+        }
+        PlainHttp::Refuse => HttpResponse::build(StatusCode::UPGRADE_REQUIRED)
+            .insert_header(("Upgrade", "TLS/1.2, HTTP/1.1"))
+            .json(ErrorResponse::new(format!(
+                "plain http is not supported, use https on port {https_port}"
+            ))),
+    }
+}
+
+// wartet auf SIGTERM (z.B. vom Orchestrator) oder SIGINT (Strg+C) und gibt den Namen des Signals zurück
+async fn shutdown_signal() -> &'static str {
+    let interrupt = Box::pin(async {
+        let _ = actix_web::rt::signal::ctrl_c().await;
+        "SIGINT"
+    });
+    #[cfg(unix)]
+    {
+        use actix_web::rt::signal::unix::{signal, SignalKind};
+
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            let terminate = Box::pin(async move {
+                terminate.recv().await;
+                "SIGTERM"
+            });
+            return future::select(interrupt, terminate).await.factor_first().0;
+        }
+    }
+    interrupt.await
+}
+
+/*
+   Fährt den Server nach einem Signal geordnet herunter: neue Verbindungen werden abgelehnt, laufende Suchen
+   dürfen bis zum Ablauf von timeout weiterrechnen und spielen danach ihren besten bisher gefundenen Zug
+*/
+async fn shut_down_on_signal(
+    handles: Vec<ServerHandle>,
+    timeout: Duration,
+    registry: web::Data<SearchRegistry>,
+) {
+    let signal = shutdown_signal().await;
+    info!(
+        "{signal} empfangen, laufende Suchen haben {} s Zeit",
+        timeout.as_secs()
+    );
+    actix_web::rt::spawn(async move {
+        actix_web::rt::time::sleep(timeout).await;
+        if registry.in_flight() > 0 {
+            warn!("{} Suchen werden abgebrochen", registry.in_flight());
+        }
+        registry.cancel_all();
+    });
+    // stop schickt den Befehl sofort, alle Server fahren also gleichzeitig herunter
+    let stopping: Vec<_> = handles.iter().map(|handle| handle.stop(true)).collect();
+    for stopped in stopping {
+        stopped.await;
     }
 }
 
 #[actix_web::main]
 async fn main() -> io::Result<()> {
-    env::set_var("RUST_LOG", "actix_web=debug,actix_server=info");
-    env_logger::init();
-    HttpServer::new(move || {
-        let cors = Cors::default()
-            .allow_any_origin()
-            .allow_any_header()
-            .allow_any_method();
-
-        App::new()
+    let config = Config::load().and_then(|config| {
+        Ok((
+            config.search_options()?,
+            config.access_control()?,
+            config.game_history()?,
+            config,
+        ))
+    });
+    let (mut search_options, access_control, game_history, config) = match config {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("error: {error}");
+            std::process::exit(2);
+        }
+    };
+    #[cfg(feature = "tls")]
+    let certificate_store = match config.certificate_store() {
+        Ok(certificate_store) => certificate_store.map(Arc::new),
+        Err(error) => {
+            eprintln!("error: {error}");
+            std::process::exit(2);
+        }
+    };
+    logging::init(&config.log_level, config.log_format);
+    if config.cors.permissive {
+        warn!("CORS ist im Entwicklungsmodus, jede Seite darf den Server ansprechen");
+    }
+
+    // Rechen-Pool für die Suche, damit "/" und "/version" auch während langer Suchen antworten
+    let pool = web::Data::new(ComputePool::new(
+        config.compute_workers,
+        config.queue_limit,
+        config.search_timeout,
+    ));
+    // jede Suche der Engine (auch in Sitzungen) fließt in die Metriken und das Log ein
+    let metrics = web::Data::new(Metrics::new());
+    let search_metrics = metrics.clone();
+    search_options.report = Some(Arc::new(move |report: &SearchReport| {
+        search_metrics.record_search(report);
+        logging::log_search(report);
+    }));
+    // alle Suchen des Servers, damit sie abgebrochen werden können
+    let search_registry = web::Data::new(SearchRegistry::new());
+    let mut session_store = SessionStore::with_search_options(search_options.clone())
+        .with_registry(search_registry.clone().into_inner())
+        .with_takeback_limits(config.takebacks.clone());
+    // beendete Spiele werden gespeichert und sind über /history abrufbar
+    let game_history = game_history.map(Arc::new);
+    if let Some(game_history) = &game_history {
+        session_store = session_store.with_history(game_history.clone());
+    }
+    let mut match_store = MatchStore::new().with_metrics(metrics.clone().into_inner());
+    if let Some(game_history) = &game_history {
+        match_store = match_store.with_history(game_history.clone());
+    }
+    let game_history = game_history.map(web::Data::from);
+    let session_store = web::Data::new(session_store);
+    let match_store = web::Data::new(match_store);
+    let spectators = web::Data::new(Spectators::new());
+    let session_snapshot = config.session_snapshot.clone();
+    if let Some(path) = &session_snapshot {
+        match session_store.load_snapshot(path) {
+            Ok(restored) => info!("{restored} Spiele aus {} wiederhergestellt", path.display()),
+            Err(error) => error!("{error}, es werden keine Spiele wiederhergestellt"),
+        }
+    }
+    let search_options = web::Data::new(search_options);
+
+    // inaktive Spiele werden regelmäßig entfernt, ihr Pondering wird dabei beendet
+    let session_timeout = config.session_timeout;
+    let expiring_store = session_store.clone();
+    let expiring_matches = match_store.clone();
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            expiring_store.expire_idle_games(session_timeout);
+            expiring_matches.expire_idle_matches(session_timeout);
+        }
+    });
+    let health = web::Data::new(Health::new());
+    let address = (config.address, config.port);
+    let workers = config.workers;
+    let shutdown_timeout = config.shutdown_timeout;
+    let shutdown_registry = search_registry.clone();
+    let shutdown_store = session_store.clone();
+    #[cfg(feature = "tls")]
+    let (http_port, plain_http_mode) = (config.http_port, config.plain_http);
+    let config = web::Data::new(config);
+    let access_control = web::Data::new(access_control);
+    let server = HttpServer::new(move || {
+        let cors = config.cors.middleware();
+
+        let mut app = App::new();
+        if let Some(game_history) = &game_history {
+            app = app.app_data(game_history.clone());
+        }
+        app
+            .app_data(session_store.clone())
+            .app_data(match_store.clone())
+            .app_data(spectators.clone())
+            .app_data(search_options.clone())
+            .app_data(pool.clone())
+            .app_data(search_registry.clone())
+            .app_data(config.clone())
+            .app_data(access_control.clone())
+            .app_data(metrics.clone())
+            .app_data(health.clone())
+            // CORS liegt außen, damit Preflight-Anfragen des Browsers ohne Schlüssel beantwortet werden
+            .wrap(from_fn(check_access))
             .wrap(cors)
-            .wrap(middleware::Logger::default())
+            .wrap(from_fn(record_request))
+            .wrap(from_fn(log_request))
             .service(status)
             .service(next_move)
             .service(version)
+            .service(levels)
+            .service(cancel_search)
+            .service(prometheus_metrics)
+            .service(healthz)
+            .service(readyz)
+            .configure(games::configure)
+            .configure(matches::configure)
+            .configure(watch::configure)
+            .configure(stream::configure)
     })
-        .bind(("0.0.0.0", 51338))?
-        .run()
-        .await
+        // schließt der Client die Verbindung, verwirft actix die Anfrage sofort, damit deren Suche abgebrochen wird
+        .h1_allow_half_closed(false)
+        .workers(workers)
+        // Signale behandelt shut_down_on_signal, actix wartet danach auf die Antworten der abgebrochenen Suchen
+        .disable_signals()
+        .shutdown_timeout(shutdown_timeout.as_secs() + SHUTDOWN_GRACE_S);
+
+    let mut servers: Vec<Server> = Vec::new();
+    #[cfg(feature = "tls")]
+    let server = match certificate_store {
+        Some(certificate_store) => {
+            tls::reload_on_sighup(certificate_store.clone());
+            servers.push(
+                server
+                    .bind_rustls_0_23(address, certificate_store.server_config())?
+                    .run(),
+            );
+            if let Some(http_port) = http_port {
+                let https_port = address.1;
+                let plain_server = HttpServer::new(move || {
+                    App::new()
+                        .wrap(from_fn(log_request))
+                        .default_service(web::to(move |request: HttpRequest| {
+                            plain_http(request, plain_http_mode, https_port)
+                        }))
+                })
+                    .workers(1)
+                    .disable_signals()
+                    .bind((address.0, http_port))?
+                    .run();
+                servers.push(plain_server);
+            }
+            None
+        }
+        None => Some(server),
+    };
+    #[cfg(not(feature = "tls"))]
+    let server = Some(server);
+    if let Some(server) = server {
+        servers.push(server.bind(address)?.run());
+    }
+
+    actix_web::rt::spawn(shut_down_on_signal(
+        servers.iter().map(Server::handle).collect(),
+        shutdown_timeout,
+        shutdown_registry,
+    ));
+    for server in servers {
+        server.await?;
+    }
+
+    // erst wenn alle Anfragen beantwortet sind, ändern sich die Spiele nicht mehr
+    if let Some(path) = &session_snapshot {
+        match shutdown_store.save_snapshot(path) {
+            Ok(saved) => info!("{saved} Spiele in {} gespeichert", path.display()),
+            Err(error) => error!("{error}"),
+        }
+    }
+    Ok(())
 }
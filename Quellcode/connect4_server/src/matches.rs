@@ -0,0 +1,234 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix_web::web::Json;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+use crate::multiplayer::{Match, MatchCommand, MatchError, MatchEvent, MatchStore, Seat};
+use crate::notation::Side;
+use crate::ErrorResponse;
+
+// Abstand der Pings an den Client und Zeit ohne Antwort, nach der die Verbindung als abgebrochen gilt
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+pub struct NewMatchInfo {
+    player: String,
+    // ohne Angabe spielt der Ersteller Rot und zieht zuerst
+    side: Option<Side>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JoinInfo {
+    code: String,
+    player: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenQuery {
+    token: String,
+}
+
+// Antwort auf das Eröffnen und Beitreten, der Token gehört nur dem jeweiligen Spieler
+#[derive(Debug, Serialize)]
+pub struct SeatResponse {
+    #[serde(flatten)]
+    seat: Seat,
+    game: Match,
+}
+
+fn error_response(error: MatchError) -> HttpResponse {
+    let body = ErrorResponse::new(&error);
+    match error {
+        MatchError::NotFound(_) | MatchError::UnknownCode(_) => HttpResponse::NotFound().json(body),
+        MatchError::InvalidToken => HttpResponse::Forbidden().json(body),
+        MatchError::Full
+        | MatchError::WaitingForOpponent
+        | MatchError::NotYourTurn
+        | MatchError::GameOver => HttpResponse::Conflict().json(body),
+        MatchError::InvalidName | MatchError::InvalidColumn(_) | MatchError::ColumnFull(_) => {
+            HttpResponse::BadRequest().json(body)
+        }
+    }
+}
+
+// eröffnet ein Spiel zwischen zwei Menschen, der Beitrittscode wird an den Gegner weitergegeben
+#[post("/matches")]
+async fn create_match(store: web::Data<MatchStore>, info: Json<NewMatchInfo>) -> impl Responder {
+    match store.create(&info.player, info.side.unwrap_or(Side::Red)) {
+        Ok((game, seat)) => HttpResponse::Created().json(SeatResponse { seat, game }),
+        Err(error) => error_response(error),
+    }
+}
+
+#[post("/matches/join")]
+async fn join_match(store: web::Data<MatchStore>, info: Json<JoinInfo>) -> impl Responder {
+    match store.join(&info.code, &info.player) {
+        Ok((game, seat)) => HttpResponse::Ok().json(SeatResponse { seat, game }),
+        Err(error) => error_response(error),
+    }
+}
+
+#[get("/matches/{id}")]
+async fn get_match(store: web::Data<MatchStore>, id: web::Path<String>) -> impl Responder {
+    match store.get(&id) {
+        Ok(game) => HttpResponse::Ok().json(game),
+        Err(error) => error_response(error),
+    }
+}
+
+/*
+   WebSocket eines Spielers, angemeldet mit dem Token aus /matches bzw. /matches/join. Der Client erhält
+   zuerst den vollständigen Stand und danach alle Ereignisse des Spiels, Züge sendet er als
+   {"type": "move", "column": 3} bzw. {"type": "resign"}. Nach einem Verbindungsabbruch verbindet er sich
+   mit demselben Token neu
+*/
+#[get("/matches/{id}/ws")]
+async fn match_socket(
+    store: web::Data<MatchStore>,
+    id: web::Path<String>,
+    query: web::Query<TokenQuery>,
+    request: HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    let id = id.into_inner();
+    let side = match store.authenticate(&id, &query.token) {
+        Ok(side) => side,
+        Err(error) => return Ok(error_response(error)),
+    };
+    let (response, session, messages) = actix_ws::handle(&request, body)?;
+    let (game, events) = match store.connect(&id, side) {
+        Ok(connected) => connected,
+        Err(error) => return Ok(error_response(error)),
+    };
+    actix_web::rt::spawn(run_socket(
+        store.into_inner(),
+        id,
+        side,
+        game,
+        session,
+        messages,
+        events,
+    ));
+    Ok(response)
+}
+
+async fn run_socket(
+    store: Arc<MatchStore>,
+    id: String,
+    side: Side,
+    game: Match,
+    mut session: Session,
+    mut messages: MessageStream,
+    mut events: Receiver<MatchEvent>,
+) {
+    let mut heartbeat = actix_web::rt::time::interval(HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+    let mut close_reason = None;
+
+    if send(&mut session, &MatchEvent::State { game }).await {
+        loop {
+            tokio::select! {
+                message = messages.recv() => {
+                    last_seen = Instant::now();
+                    let reply = match message {
+                        Some(Ok(Message::Text(text))) => {
+                            handle_command(&store, &id, side, &text).await
+                        }
+                        Some(Ok(Message::Ping(bytes))) => {
+                            if session.pong(&bytes).await.is_err() {
+                                break;
+                            }
+                            None
+                        }
+                        Some(Ok(Message::Close(reason))) => {
+                            close_reason = reason;
+                            break;
+                        }
+                        Some(Ok(_)) => None,
+                        Some(Err(_)) | None => break,
+                    };
+                    if let Some(event) = reply {
+                        if !send(&mut session, &event).await {
+                            break;
+                        }
+                    }
+                }
+                event = events.recv() => {
+                    let event = match event {
+                        Ok(event) => event,
+                        // der Client war zu langsam und hat Ereignisse verpasst, er erhält den vollständigen Stand
+                        Err(RecvError::Lagged(_)) => match store.get(&id) {
+                            Ok(game) => MatchEvent::State { game },
+                            Err(_) => break,
+                        },
+                        // das Spiel wurde entfernt
+                        Err(RecvError::Closed) => {
+                            close_reason = Some(CloseReason {
+                                code: CloseCode::Away,
+                                description: Some("match expired".to_string()),
+                            });
+                            break;
+                        }
+                    };
+                    if !send(&mut session, &event).await {
+                        break;
+                    }
+                }
+                _ = heartbeat.tick() => {
+                    if last_seen.elapsed() > CLIENT_TIMEOUT {
+                        break;
+                    }
+                    if session.ping(b"").await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    store.disconnect(&id, side);
+    let _ = session.close(close_reason).await;
+}
+
+/*
+   Führt einen Befehl des Clients aus, abgelehnte Befehle werden nur diesem Client gemeldet. Das Ende
+   eines Spiels schreibt in den Verlauf, deshalb läuft der Befehl nicht auf dem Worker des Sockets
+*/
+async fn handle_command(
+    store: &Arc<MatchStore>,
+    id: &str,
+    side: Side,
+    text: &str,
+) -> Option<MatchEvent> {
+    let result = match serde_json::from_str::<MatchCommand>(text) {
+        Ok(command) => {
+            let store = store.clone();
+            let id = id.to_string();
+            match web::block(move || store.apply(&id, side, command)).await {
+                Ok(applied) => applied.map_err(|error| error.to_string()),
+                Err(error) => Err(error.to_string()),
+            }
+        }
+        Err(error) => Err(format!("invalid command: {error}")),
+    };
+    result.err().map(|message| MatchEvent::Error { message })
+}
+
+// false, wenn der Client die Verbindung bereits geschlossen hat
+pub async fn send(session: &mut Session, event: &impl Serialize) -> bool {
+    let text = serde_json::to_string(event).unwrap();
+    session.text(text).await.is_ok()
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(create_match)
+        .service(join_match)
+        .service(get_match)
+        .service(match_socket);
+}
@@ -0,0 +1,342 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::connect4ai::{NextMoveResult, SearchReport};
+use crate::notation::Outcome;
+
+// obere Grenzen der Buckets in Sekunden bzw. Halbzügen
+const HTTP_DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+const SEARCH_DURATION_BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+const DEPTH_BUCKETS: [f64; 11] = [1.0, 2.0, 4.0, 6.0, 8.0, 10.0, 12.0, 16.0, 20.0, 30.0, 42.0];
+
+// HTTP-Methoden, die eine eigene Reihe erhalten, alle anderen werden als "other" gezählt
+const HTTP_METHODS: [&str; 7] = ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Histogram {
+        Histogram {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    // Buckets sind kumulativ, "+Inf" entspricht der Gesamtzahl
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{labels}{separator}le=\"{bound}\"}} {count}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {}",
+            self.count
+        );
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {}", self.count);
+    }
+}
+
+// Zähler einer Stufe, der als eigene Reihe ausgegeben wird
+type Counter = fn(&SearchMetrics) -> u64;
+
+// Kennzahlen aller Suchen einer Stufe
+struct SearchMetrics {
+    duration: Histogram,
+    depth: Histogram,
+    nodes: u64,
+    book_moves: u64,
+    cancelled: u64,
+    evaluation_cache_hits: u64,
+    evaluation_cache_misses: u64,
+    tt_probes: u64,
+    tt_hits: u64,
+}
+
+impl SearchMetrics {
+    fn new() -> SearchMetrics {
+        SearchMetrics {
+            duration: Histogram::new(&SEARCH_DURATION_BUCKETS),
+            depth: Histogram::new(&DEPTH_BUCKETS),
+            nodes: 0,
+            book_moves: 0,
+            cancelled: 0,
+            evaluation_cache_hits: 0,
+            evaluation_cache_misses: 0,
+            tt_probes: 0,
+            tt_hits: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct MetricsState {
+    // (Methode, Route, Status)
+    http_requests: BTreeMap<(String, String, u16), u64>,
+    // (Methode, Route)
+    http_durations: BTreeMap<(String, String), Histogram>,
+    // Stufe der Schwierigkeit, "custom" für eigens zusammengestellte Schwierigkeiten
+    searches: BTreeMap<String, SearchMetrics>,
+    // (Art des Spiels, Ergebnis): "engine" für Spiele gegen die Engine, "match" für Spiele zwischen zwei Menschen
+    games_finished: BTreeMap<(&'static str, &'static str), u64>,
+}
+
+/*
+   Kennzahlen des Servers im Textformat von Prometheus (GET /metrics).
+   Routen werden als Muster wie "/games/{id}" gezählt, damit nicht jede Spiel-Id eine eigene Reihe erzeugt,
+   aus demselben Grund werden unbekannte HTTP-Methoden zusammengefasst
+*/
+#[derive(Default)]
+pub struct Metrics {
+    state: Mutex<MetricsState>,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    pub fn record_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let method = HTTP_METHODS
+            .into_iter()
+            .find(|known| *known == method)
+            .unwrap_or("other");
+        let mut state = self.state.lock().unwrap();
+        *state
+            .http_requests
+            .entry((method.to_string(), route.to_string(), status))
+            .or_default() += 1;
+        state
+            .http_durations
+            .entry((method.to_string(), route.to_string()))
+            .or_insert_with(|| Histogram::new(&HTTP_DURATION_BUCKETS))
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_search(&self, report: &SearchReport) {
+        let level = report
+            .level
+            .map_or_else(|| "custom".to_string(), |level| level.to_string());
+        let mut state = self.state.lock().unwrap();
+        let search = state
+            .searches
+            .entry(level)
+            .or_insert_with(SearchMetrics::new);
+        search.duration.observe(report.elapsed_ms as f64 / 1000.0);
+        if report.book {
+            search.book_moves += 1;
+        } else {
+            search.depth.observe(report.depth as f64);
+        }
+        search.cancelled += report.cancelled as u64;
+        search.nodes += report.nodes;
+        search.evaluation_cache_hits += report.evaluation_cache_hits;
+        search.evaluation_cache_misses += report.evaluation_cache_misses;
+        search.tt_probes += report.tt_probes;
+        search.tt_hits += report.tt_hits;
+    }
+
+    /*
+       zählt ein beendetes Spiel gegen die Engine, Ergebnisse ohne Spielende werden ignoriert. Nur Spiele, die
+       der Server selbst führt, werden gezählt, Stellungen an /next_move kann jeder Client beliebig oft schicken
+    */
+    pub fn record_game_finished(&self, result: NextMoveResult) {
+        let result = match result {
+            NextMoveResult::ComputerWins => "computer_wins",
+            NextMoveResult::PlayerWins => "player_wins",
+            NextMoveResult::Draw => "draw",
+            NextMoveResult::NextMove | NextMoveResult::None => return,
+        };
+        self.count_game("engine", result);
+    }
+
+    // zählt ein beendetes Spiel zwischen zwei Menschen
+    pub fn record_match_finished(&self, outcome: Outcome) {
+        let result = match outcome {
+            Outcome::RedWins => "red_wins",
+            Outcome::YellowWins => "yellow_wins",
+            Outcome::Draw => "draw",
+            Outcome::Ongoing => return,
+        };
+        self.count_game("match", result);
+    }
+
+    fn count_game(&self, mode: &'static str, result: &'static str) {
+        *self
+            .state
+            .lock()
+            .unwrap()
+            .games_finished
+            .entry((mode, result))
+            .or_default() += 1;
+    }
+
+    pub fn render(&self, active_sessions: usize, pondering_games: usize) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "connect4_http_requests_total",
+            "counter",
+            "HTTP requests by route and status",
+        );
+        for ((method, route, status), count) in &state.http_requests {
+            let _ = writeln!(
+                out,
+                "connect4_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{status}\"}} {count}",
+                escape(method),
+                escape(route)
+            );
+        }
+        header(
+            &mut out,
+            "connect4_http_request_duration_seconds",
+            "histogram",
+            "HTTP request latency by route",
+        );
+        for ((method, route), histogram) in &state.http_durations {
+            let labels = format!("method=\"{}\",route=\"{}\"", escape(method), escape(route));
+            histogram.write(&mut out, "connect4_http_request_duration_seconds", &labels);
+        }
+
+        header(
+            &mut out,
+            "connect4_search_duration_seconds",
+            "histogram",
+            "Engine search duration by level",
+        );
+        for (level, search) in &state.searches {
+            search.duration.write(
+                &mut out,
+                "connect4_search_duration_seconds",
+                &format!("level=\"{level}\""),
+            );
+        }
+        header(
+            &mut out,
+            "connect4_search_depth",
+            "histogram",
+            "Depth of the last completed iteration by level",
+        );
+        for (level, search) in &state.searches {
+            search.depth.write(
+                &mut out,
+                "connect4_search_depth",
+                &format!("level=\"{level}\""),
+            );
+        }
+        let counters: [(&str, &str, Counter); 7] = [
+            (
+                "connect4_search_nodes_total",
+                "Positions searched",
+                |search| search.nodes,
+            ),
+            (
+                "connect4_book_moves_total",
+                "Moves played from the opening book",
+                |search| search.book_moves,
+            ),
+            (
+                "connect4_cancelled_searches_total",
+                "Searches cancelled before completion",
+                |search| search.cancelled,
+            ),
+            (
+                "connect4_evaluation_cache_hits_total",
+                "Evaluation cache hits",
+                |search| search.evaluation_cache_hits,
+            ),
+            (
+                "connect4_evaluation_cache_misses_total",
+                "Evaluation cache misses",
+                |search| search.evaluation_cache_misses,
+            ),
+            (
+                "connect4_transposition_table_probes_total",
+                "Transposition table probes",
+                |search| search.tt_probes,
+            ),
+            (
+                "connect4_transposition_table_hits_total",
+                "Transposition table hits",
+                |search| search.tt_hits,
+            ),
+        ];
+        for (name, help, value) in counters {
+            header(&mut out, name, "counter", help);
+            for (level, search) in &state.searches {
+                let _ = writeln!(out, "{name}{{level=\"{level}\"}} {}", value(search));
+            }
+        }
+
+        header(
+            &mut out,
+            "connect4_games_finished_total",
+            "counter",
+            "Finished games by mode and result",
+        );
+        for ((mode, result), count) in &state.games_finished {
+            let _ = writeln!(
+                out,
+                "connect4_games_finished_total{{mode=\"{mode}\",result=\"{result}\"}} {count}"
+            );
+        }
+        header(
+            &mut out,
+            "connect4_active_sessions",
+            "gauge",
+            "Session games held in memory",
+        );
+        let _ = writeln!(out, "connect4_active_sessions {active_sessions}");
+        header(
+            &mut out,
+            "connect4_pondering_games",
+            "gauge",
+            "Session games pondering in the background",
+        );
+        let _ = writeln!(out, "connect4_pondering_games {pondering_games}");
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
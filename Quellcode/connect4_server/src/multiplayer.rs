@@ -0,0 +1,583 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::access::constant_time_eq;
+use crate::connect4ai::{
+    available_fields, check_for_row, GameBoard, COMPUTER_PLAYER, USER_PLAYER, WIDTH,
+};
+use crate::history::{GameHistory, GameRecord, RecordedMove};
+use crate::metrics::Metrics;
+use crate::notation::{Outcome, Side};
+use crate::session::{is_valid_player_name, unix_time, MAX_PLAYER_NAME_LENGTH};
+
+// Zeichen der Beitrittscodes, ohne leicht verwechselbare Zeichen wie 0/O und 1/I
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 6;
+// Ereignisse, die ein langsamer Client verpassen darf, bevor er den vollständigen Stand erhält
+const EVENT_CAPACITY: usize = 64;
+
+#[derive(Debug, PartialEq)]
+pub enum MatchError {
+    NotFound(String),
+    UnknownCode(String),
+    Full,
+    InvalidToken,
+    InvalidName,
+    WaitingForOpponent,
+    NotYourTurn,
+    GameOver,
+    InvalidColumn(u8),
+    ColumnFull(u8),
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchError::NotFound(id) => write!(f, "match {id} not found"),
+            MatchError::UnknownCode(code) => write!(f, "no open match with code {code}"),
+            MatchError::Full => write!(f, "match already has two players"),
+            MatchError::InvalidToken => write!(f, "invalid player token"),
+            MatchError::InvalidName => write!(
+                f,
+                "player name must have 1 to {MAX_PLAYER_NAME_LENGTH} characters"
+            ),
+            MatchError::WaitingForOpponent => write!(f, "waiting for an opponent to join"),
+            MatchError::NotYourTurn => write!(f, "it is not your turn"),
+            MatchError::GameOver => write!(f, "game is already over"),
+            MatchError::InvalidColumn(column) => {
+                write!(f, "invalid column {column}, expected 0 to {}", WIDTH - 1)
+            }
+            MatchError::ColumnFull(column) => write!(f, "column {column} is full"),
+        }
+    }
+}
+
+impl std::error::Error for MatchError {}
+
+/*
+   Ein Spiel zwischen zwei Menschen, Rot zieht zuerst
+    - code: Beitrittscode für den zweiten Spieler, None sobald beide Plätze besetzt sind
+    - red / yellow: Namen der Spieler, None solange der Platz frei ist
+    - red_connected / yellow_connected: ob der Spieler gerade per WebSocket verbunden ist
+    - resigned: Seite, die aufgegeben hat
+    - started_at / finished_at: Unix-Zeit in Sekunden
+*/
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Match {
+    pub id: String,
+    pub code: Option<String>,
+    pub red: Option<String>,
+    pub yellow: Option<String>,
+    pub board: GameBoard,
+    pub moves: Vec<u8>,
+    pub to_move: Side,
+    pub outcome: Outcome,
+    pub resigned: Option<Side>,
+    pub red_connected: bool,
+    pub yellow_connected: bool,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+}
+
+impl Match {
+    pub fn is_over(&self) -> bool {
+        self.outcome != Outcome::Ongoing
+    }
+
+    pub fn player(&self, side: Side) -> Option<&str> {
+        match side {
+            Side::Red => self.red.as_deref(),
+            Side::Yellow => self.yellow.as_deref(),
+        }
+    }
+
+    fn set_connected(&mut self, side: Side, connected: bool) {
+        match side {
+            Side::Red => self.red_connected = connected,
+            Side::Yellow => self.yellow_connected = connected,
+        }
+    }
+
+    // Eintrag für den Verlauf: Rot ist player, Gelb opponent
+    fn record(&self) -> Option<GameRecord> {
+        let result = match self.outcome {
+            Outcome::RedWins => "player_wins",
+            Outcome::YellowWins => "opponent_wins",
+            Outcome::Draw => "draw",
+            Outcome::Ongoing => return None,
+        };
+        Some(GameRecord {
+            id: self.id.clone(),
+            player: self.red.clone()?,
+            opponent: self.yellow.clone(),
+            level: None,
+            adaptive: false,
+            computer_started: false,
+            seed: 0,
+            result: result.to_string(),
+            started_at: self.started_at,
+            finished_at: self.finished_at?,
+            takebacks: 0,
+            moves: self
+                .moves
+                .iter()
+                .map(|&column| RecordedMove {
+                    column,
+                    score: None,
+                })
+                .collect(),
+        })
+    }
+}
+
+// Platz eines Spielers, mit dem Token kann er sich nach einem Verbindungsabbruch wieder verbinden
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Seat {
+    pub side: Side,
+    pub token: String,
+}
+
+/*
+   Nachrichten an die Clients eines Spiels
+    - state: vollständiger Stand, direkt nach dem Verbinden und wenn ein Client Ereignisse verpasst hat
+    - error: nur an den Client, dessen Befehl abgelehnt wurde
+*/
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MatchEvent {
+    State {
+        game: Match,
+    },
+    Joined {
+        side: Side,
+        player: String,
+    },
+    Move {
+        ply: usize,
+        side: Side,
+        column: u8,
+        row: u8,
+    },
+    GameOver {
+        outcome: Outcome,
+        resigned: Option<Side>,
+    },
+    Presence {
+        side: Side,
+        connected: bool,
+    },
+    Error {
+        message: String,
+    },
+}
+
+// Befehle eines Spielers, z.B. {"type": "move", "column": 3}
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MatchCommand {
+    Move { column: u8 },
+    Resign,
+}
+
+/*
+   Ein Spiel im Speicher
+    - tokens: geheime Tokens beider Plätze, Gelb erhält seinen Token beim Beitreten
+    - connections: Anzahl offener WebSockets je Seite, ein Spieler darf mehrere Fenster offen haben
+    - events: Ereignisse für alle verbundenen Clients
+*/
+struct MatchEntry {
+    game: Mutex<Match>,
+    tokens: [String; 2],
+    connections: Mutex<[usize; 2]>,
+    events: broadcast::Sender<MatchEvent>,
+    last_activity: Mutex<Instant>,
+}
+
+impl MatchEntry {
+    fn publish(&self, event: MatchEvent) {
+        // ohne verbundene Clients gibt es niemanden zu benachrichtigen
+        let _ = self.events.send(event);
+    }
+
+    fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+}
+
+/*
+   Hält alle Spiele zwischen zwei Menschen. Die Züge kommen über WebSockets (siehe main.rs),
+   die Regeln werden hier geprüft: Zugreihenfolge, gültige Spalten und Spielende
+*/
+#[derive(Default)]
+pub struct MatchStore {
+    matches: Mutex<HashMap<String, Arc<MatchEntry>>>,
+    // Beitrittscode -> Id des Spiels, solange ein Platz frei ist
+    codes: Mutex<HashMap<String, String>>,
+    history: Option<Arc<GameHistory>>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl MatchStore {
+    pub fn new() -> MatchStore {
+        MatchStore::default()
+    }
+
+    // beendete Spiele werden im Verlauf gespeichert
+    pub fn with_history(self, history: Arc<GameHistory>) -> MatchStore {
+        MatchStore {
+            history: Some(history),
+            ..self
+        }
+    }
+
+    // beendete Spiele werden in den Metriken gezählt
+    pub fn with_metrics(self, metrics: Arc<Metrics>) -> MatchStore {
+        MatchStore {
+            metrics: Some(metrics),
+            ..self
+        }
+    }
+
+    // eröffnet ein Spiel, der andere Platz wird über den Beitrittscode vergeben
+    pub fn create(&self, player: &str, side: Side) -> Result<(Match, Seat), MatchError> {
+        check_name(player)?;
+        let mut codes = self.codes.lock().unwrap();
+        let code = loop {
+            let code = random_code();
+            if !codes.contains_key(&code) {
+                break code;
+            }
+        };
+
+        let mut game = Match {
+            id: format!("{:016x}", rand::random::<u64>()),
+            code: Some(code.clone()),
+            red: None,
+            yellow: None,
+            board: GameBoard::new(),
+            moves: Vec::new(),
+            to_move: Side::Red,
+            outcome: Outcome::Ongoing,
+            resigned: None,
+            red_connected: false,
+            yellow_connected: false,
+            started_at: unix_time(),
+            finished_at: None,
+        };
+        match side {
+            Side::Red => game.red = Some(player.to_string()),
+            Side::Yellow => game.yellow = Some(player.to_string()),
+        }
+
+        let tokens = [random_token(), random_token()];
+        let seat = Seat {
+            side,
+            token: tokens[index(side)].clone(),
+        };
+        let entry = Arc::new(MatchEntry {
+            game: Mutex::new(game.clone()),
+            tokens,
+            connections: Mutex::new([0, 0]),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            last_activity: Mutex::new(Instant::now()),
+        });
+        codes.insert(code, game.id.clone());
+        self.matches.lock().unwrap().insert(game.id.clone(), entry);
+        Ok((game, seat))
+    }
+
+    // besetzt den freien Platz, Groß- und Kleinschreibung des Codes spielen keine Rolle
+    pub fn join(&self, code: &str, player: &str) -> Result<(Match, Seat), MatchError> {
+        // vor dem Entfernen des Codes, ein abgelehnter Name verbraucht ihn nicht
+        check_name(player)?;
+        let code = code.trim().to_uppercase();
+        let id = self
+            .codes
+            .lock()
+            .unwrap()
+            .remove(&code)
+            .ok_or_else(|| MatchError::UnknownCode(code.clone()))?;
+        let entry = self.handle(&id)?;
+        let mut game = entry.game.lock().unwrap();
+        let side = match (&game.red, &game.yellow) {
+            (None, _) => Side::Red,
+            (_, None) => Side::Yellow,
+            _ => return Err(MatchError::Full),
+        };
+
+        match side {
+            Side::Red => game.red = Some(player.to_string()),
+            Side::Yellow => game.yellow = Some(player.to_string()),
+        }
+        game.code = None;
+        entry.touch();
+        entry.publish(MatchEvent::Joined {
+            side,
+            player: player.to_string(),
+        });
+        let seat = Seat {
+            side,
+            token: entry.tokens[index(side)].clone(),
+        };
+        Ok((game.clone(), seat))
+    }
+
+    pub fn get(&self, id: &str) -> Result<Match, MatchError> {
+        Ok(self.handle(id)?.game.lock().unwrap().clone())
+    }
+
+    // Seite des Spielers mit diesem Token
+    pub fn authenticate(&self, id: &str, token: &str) -> Result<Side, MatchError> {
+        let entry = self.handle(id)?;
+        let side = [Side::Red, Side::Yellow]
+            .into_iter()
+            .find(|side| constant_time_eq(&entry.tokens[index(*side)], token))
+            .ok_or(MatchError::InvalidToken)?;
+        // der Token des freien Platzes gilt erst, wenn ein Spieler beigetreten ist
+        let seated = entry.game.lock().unwrap().player(side).is_some();
+        if seated {
+            Ok(side)
+        } else {
+            Err(MatchError::InvalidToken)
+        }
+    }
+
+    /*
+       Meldet einen WebSocket der Seite an: gibt den aktuellen Stand und den Empfänger aller folgenden
+       Ereignisse zurück. Die erste Verbindung einer Seite wird den übrigen Clients gemeldet
+    */
+    pub fn connect(
+        &self,
+        id: &str,
+        side: Side,
+    ) -> Result<(Match, broadcast::Receiver<MatchEvent>), MatchError> {
+        let entry = self.handle(id)?;
+        let mut game = entry.game.lock().unwrap();
+        let mut connections = entry.connections.lock().unwrap();
+        connections[index(side)] += 1;
+        if connections[index(side)] == 1 {
+            game.set_connected(side, true);
+            entry.publish(MatchEvent::Presence {
+                side,
+                connected: true,
+            });
+        }
+        entry.touch();
+        // erst nach der eigenen Meldung, der Stand enthält sie bereits
+        let events = entry.events.subscribe();
+        Ok((game.clone(), events))
+    }
+
+    // Stand und Ereignisse für Zuschauer, sie zählen nicht als verbundene Spieler
+    pub fn watch(&self, id: &str) -> Result<(Match, broadcast::Receiver<MatchEvent>), MatchError> {
+        let entry = self.handle(id)?;
+        let game = entry.game.lock().unwrap();
+        Ok((game.clone(), entry.events.subscribe()))
+    }
+
+    // meldet einen WebSocket ab, ist es der letzte der Seite, erfahren die übrigen Clients davon
+    pub fn disconnect(&self, id: &str, side: Side) {
+        let Ok(entry) = self.handle(id) else {
+            return;
+        };
+        let mut game = entry.game.lock().unwrap();
+        let mut connections = entry.connections.lock().unwrap();
+        connections[index(side)] = connections[index(side)].saturating_sub(1);
+        if connections[index(side)] == 0 {
+            game.set_connected(side, false);
+            entry.publish(MatchEvent::Presence {
+                side,
+                connected: false,
+            });
+        }
+    }
+
+    pub fn apply(&self, id: &str, side: Side, command: MatchCommand) -> Result<Match, MatchError> {
+        match command {
+            MatchCommand::Move { column } => self.play(id, side, column),
+            MatchCommand::Resign => self.resign(id, side),
+        }
+    }
+
+    // führt den Zug aus, wenn die Seite am Zug ist und die Spalte noch frei ist
+    pub fn play(&self, id: &str, side: Side, column: u8) -> Result<Match, MatchError> {
+        let entry = self.handle(id)?;
+        let mut game = entry.game.lock().unwrap();
+        check_playable(&game)?;
+        if game.to_move != side {
+            return Err(MatchError::NotYourTurn);
+        }
+        if column as usize >= WIDTH {
+            return Err(MatchError::InvalidColumn(column));
+        }
+        let field = available_fields(&game.board)
+            .into_iter()
+            .find(|field| field.x == column)
+            .ok_or(MatchError::ColumnFull(column))?;
+
+        let chip = chip(side);
+        game.board.set(field.x as usize, field.y as usize, chip);
+        game.moves.push(column);
+        game.to_move = side.other();
+        entry.touch();
+        entry.publish(MatchEvent::Move {
+            ply: game.moves.len(),
+            side,
+            column,
+            row: field.y,
+        });
+
+        let record = if check_for_row(&game.board.grid, chip, 4).0 {
+            self.finish(&entry, &mut game, Outcome::win(side), None)
+        } else if available_fields(&game.board).is_empty() {
+            self.finish(&entry, &mut game, Outcome::Draw, None)
+        } else {
+            None
+        };
+        let finished = game.clone();
+        drop(game);
+        self.save(record);
+        Ok(finished)
+    }
+
+    pub fn resign(&self, id: &str, side: Side) -> Result<Match, MatchError> {
+        let entry = self.handle(id)?;
+        let mut game = entry.game.lock().unwrap();
+        check_playable(&game)?;
+        entry.touch();
+        let record = self.finish(&entry, &mut game, Outcome::win(side.other()), Some(side));
+        let finished = game.clone();
+        drop(game);
+        self.save(record);
+        Ok(finished)
+    }
+
+    /*
+       Entfernt Spiele, in denen länger als max_idle nichts passiert ist und kein Spieler verbunden ist,
+       und gibt deren Anzahl zurück. Die Clients erfahren davon durch das Ende des Ereignis-Kanals
+    */
+    pub fn expire_idle_matches(&self, max_idle: Duration) -> usize {
+        let mut matches = self.matches.lock().unwrap();
+        let expired: Vec<String> = matches
+            .iter()
+            .filter(|(_, entry)| {
+                entry.last_activity.lock().unwrap().elapsed() >= max_idle
+                    && *entry.connections.lock().unwrap() == [0, 0]
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        let codes: Vec<String> = expired
+            .iter()
+            .filter_map(|id| matches.remove(id))
+            .filter_map(|entry| entry.game.lock().unwrap().code.clone())
+            .collect();
+        // create sperrt zuerst die Codes, deshalb erst nach der Freigabe der Spiele
+        drop(matches);
+        let mut open_codes = self.codes.lock().unwrap();
+        for code in codes {
+            open_codes.remove(&code);
+        }
+        expired.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.matches.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // beendet das Spiel unter der Sperre, die Aufzeichnung wird erst nach deren Freigabe gespeichert
+    fn finish(
+        &self,
+        entry: &MatchEntry,
+        game: &mut Match,
+        outcome: Outcome,
+        resigned: Option<Side>,
+    ) -> Option<GameRecord> {
+        game.outcome = outcome;
+        game.resigned = resigned;
+        game.finished_at = Some(unix_time());
+        entry.publish(MatchEvent::GameOver { outcome, resigned });
+        if let Some(metrics) = &self.metrics {
+            metrics.record_match_finished(outcome);
+        }
+
+        self.history.as_ref().and_then(|_| game.record())
+    }
+
+    // ein Fehler beim Speichern beendet das Spiel nicht, es fehlt dann nur im Verlauf
+    fn save(&self, record: Option<GameRecord>) {
+        let (Some(history), Some(record)) = (&self.history, record) else {
+            return;
+        };
+        if let Err(error) = history.record(&record) {
+            log::error!("Spiel {} wurde nicht gespeichert: {error}", record.id);
+        }
+    }
+
+    fn handle(&self, id: &str) -> Result<Arc<MatchEntry>, MatchError> {
+        self.matches
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| MatchError::NotFound(id.to_string()))
+    }
+}
+
+fn check_playable(game: &Match) -> Result<(), MatchError> {
+    if game.is_over() {
+        return Err(MatchError::GameOver);
+    }
+    if game.red.is_none() || game.yellow.is_none() {
+        return Err(MatchError::WaitingForOpponent);
+    }
+    Ok(())
+}
+
+fn check_name(player: &str) -> Result<(), MatchError> {
+    if !is_valid_player_name(player) {
+        return Err(MatchError::InvalidName);
+    }
+    Ok(())
+}
+
+fn index(side: Side) -> usize {
+    match side {
+        Side::Red => 0,
+        Side::Yellow => 1,
+    }
+}
+
+// Steine wie beim Nachspielen einer Aufzeichnung: Rot wie der Nutzer, Gelb wie der Computer
+fn chip(side: Side) -> u8 {
+    match side {
+        Side::Red => USER_PLAYER,
+        Side::Yellow => COMPUTER_PLAYER,
+    }
+}
+
+fn random_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..CODE_LENGTH)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+fn random_token() -> String {
+    format!(
+        "{:016x}{:016x}",
+        rand::random::<u64>(),
+        rand::random::<u64>()
+    )
+}
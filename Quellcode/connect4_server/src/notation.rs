@@ -0,0 +1,738 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::connect4ai::{
+    available_fields, check_for_row, GameBoard, COMPUTER_PLAYER, HEIGHT, USER_PLAYER, WIDTH,
+};
+use crate::history::{GameRecord, RecordedMove};
+use crate::session::{unix_time, Game};
+
+pub const VARIANT: &str = "Standard";
+// Name, unter dem die Engine als Spieler eingetragen wird
+pub const ENGINE_NAME: &str = "Engine";
+// Zeilenlänge des Zugteils, längere Zeilen werden umbrochen
+const LINE_WIDTH: usize = 79;
+
+#[derive(Debug, PartialEq)]
+pub enum NotationError {
+    // Zeilennummer (ab 1) und Beschreibung des Fehlers
+    Syntax(usize, String),
+    Tag(String),
+    // Halbzug (ab 1) und Beschreibung des Fehlers
+    IllegalMove(usize, String),
+    Result(String),
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotationError::Syntax(line, error) => write!(f, "line {line}: {error}"),
+            NotationError::Tag(error) => write!(f, "invalid tag: {error}"),
+            NotationError::IllegalMove(ply, error) => write!(f, "move {ply}: {error}"),
+            NotationError::Result(error) => write!(f, "invalid result: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+/*
+   Ein Zug der Aufzeichnung
+    - column: Spalte 0-6, notiert als Buchstabe a-g
+    - score: Bewertung der Engine nach ihrem Zug, notiert als [%score N] im Kommentar
+    - comment: freier Kommentar zum Zug
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NotatedMove {
+    pub column: u8,
+    pub score: Option<i64>,
+    pub comment: Option<String>,
+}
+
+// Rot zieht immer zuerst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Red,
+    Yellow,
+}
+
+impl Side {
+    // Seite, die im übergebenen Halbzug (ab 0) zieht
+    pub fn to_move(ply: usize) -> Side {
+        if ply.is_multiple_of(2) {
+            Side::Red
+        } else {
+            Side::Yellow
+        }
+    }
+
+    pub fn other(self) -> Side {
+        match self {
+            Side::Red => Side::Yellow,
+            Side::Yellow => Side::Red,
+        }
+    }
+}
+
+// Ausgang einer Partie
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    RedWins,
+    YellowWins,
+    Draw,
+    Ongoing,
+}
+
+impl Outcome {
+    pub fn win(side: Side) -> Outcome {
+        match side {
+            Side::Red => Outcome::RedWins,
+            Side::Yellow => Outcome::YellowWins,
+        }
+    }
+
+    pub fn token(self) -> &'static str {
+        match self {
+            Outcome::RedWins => "1-0",
+            Outcome::YellowWins => "0-1",
+            Outcome::Draw => "1/2-1/2",
+            Outcome::Ongoing => "*",
+        }
+    }
+
+    fn from_token(token: &str) -> Option<Outcome> {
+        [
+            Outcome::RedWins,
+            Outcome::YellowWins,
+            Outcome::Draw,
+            Outcome::Ongoing,
+        ]
+        .into_iter()
+        .find(|outcome| outcome.token() == token)
+    }
+}
+
+/*
+   Aufzeichnung einer Partie in einem an PGN angelehnten Textformat, z.B.
+
+       [Event "Connect Four"]
+       [Date "2026.10.18"]
+       [Red "anna"]
+       [Yellow "Engine"]
+       [Result "0-1"]
+
+       1. d e {[%score 0] ruhige Antwort} 2. d d 0-1
+
+   Kopfzeilen enthalten Tags wie Date, Time (UTC), Red, Yellow, Computer (Red oder Yellow), Level, Seed,
+   Takebacks, Variant, Board und Result. Rot zieht zuerst, Spalten werden von links als a-g notiert
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameNotation {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<NotatedMove>,
+}
+
+// Ergebnis des Nachspielens: Brett nach dem letzten Zug und Ausgang der Partie
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub board: GameBoard,
+    pub outcome: Outcome,
+}
+
+impl GameNotation {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // setzt einen Tag, ein vorhandener Tag mit demselben Namen wird ersetzt
+    pub fn set_tag(&mut self, name: &str, value: impl ToString) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, current)) => *current = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    // laufendes oder beendetes Spiel gegen die Engine
+    pub fn from_game(game: &Game) -> GameNotation {
+        let moves = game
+            .moves
+            .iter()
+            .enumerate()
+            .map(|(ply, &column)| RecordedMove {
+                column,
+                score: game.scores.get(ply).copied().flatten(),
+            })
+            .collect::<Vec<_>>();
+        let outcome = replay_moves(&moves, game.computer_started)
+            .map_or(Outcome::Ongoing, |replay| replay.outcome);
+        notation(
+            &game.id,
+            &game.player,
+            None,
+            Some(game.level),
+            game.computer_started,
+            game.seed,
+            game.started_at,
+            game.takebacks,
+            &moves,
+            outcome,
+        )
+    }
+
+    pub fn from_record(record: &GameRecord) -> GameNotation {
+        let outcome = replay_moves(&record.moves, record.computer_started)
+            .map_or(Outcome::Ongoing, |replay| replay.outcome);
+        notation(
+            &record.id,
+            &record.player,
+            record.opponent.as_deref(),
+            record.level,
+            record.computer_started,
+            record.seed,
+            record.started_at,
+            record.takebacks,
+            &record.moves,
+            outcome,
+        )
+    }
+
+    pub fn parse(text: &str) -> Result<GameNotation, NotationError> {
+        let mut notation = GameNotation::default();
+        let mut lines = text.lines().enumerate().peekable();
+
+        while let Some((index, line)) = lines.peek() {
+            let line = line.trim();
+            if line.is_empty() {
+                lines.next();
+                continue;
+            }
+            if !line.starts_with('[') {
+                break;
+            }
+            let (name, value) =
+                parse_tag(line).map_err(|error| NotationError::Syntax(index + 1, error))?;
+            if notation.tag(&name).is_some() {
+                return Err(NotationError::Syntax(
+                    index + 1,
+                    format!("duplicate tag {name}"),
+                ));
+            }
+            notation.tags.push((name, value));
+            lines.next();
+        }
+
+        let mut result = None;
+        let mut comment: Option<(usize, String)> = None;
+        for (index, line) in lines {
+            let line_number = index + 1;
+            let mut rest = line;
+            while !rest.is_empty() {
+                // Kommentare dürfen über mehrere Zeilen gehen
+                if let Some((_, text)) = &mut comment {
+                    match rest.find('}') {
+                        Some(end) => {
+                            text.push_str(&rest[..end]);
+                            let (_, text) = comment.take().unwrap();
+                            attach_comment(&mut notation.moves, &text)
+                                .map_err(|error| NotationError::Syntax(line_number, error))?;
+                            rest = &rest[end + 1..];
+                        }
+                        None => {
+                            text.push_str(rest);
+                            text.push(' ');
+                            rest = "";
+                        }
+                    }
+                    continue;
+                }
+
+                rest = rest.trim_start();
+                if rest.is_empty() {
+                    break;
+                }
+                if let Some(after) = rest.strip_prefix('{') {
+                    comment = Some((line_number, String::new()));
+                    rest = after;
+                    continue;
+                }
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || c == '{')
+                    .unwrap_or(rest.len());
+                let token = &rest[..end];
+                rest = &rest[end..];
+
+                if result.is_some() {
+                    return Err(NotationError::Syntax(
+                        line_number,
+                        format!("unexpected '{token}' after the result"),
+                    ));
+                }
+                if let Some(outcome) = Outcome::from_token(token) {
+                    result = Some(outcome);
+                } else if is_move_number(token) {
+                    continue;
+                } else {
+                    let column = parse_column(token).ok_or_else(|| {
+                        NotationError::Syntax(line_number, format!("invalid move '{token}'"))
+                    })?;
+                    notation.moves.push(NotatedMove {
+                        column,
+                        ..NotatedMove::default()
+                    });
+                }
+            }
+        }
+        if let Some((line, _)) = comment {
+            return Err(NotationError::Syntax(
+                line,
+                "unterminated comment".to_string(),
+            ));
+        }
+
+        // das Ergebnis hinter den Zügen muss zum Tag passen, fehlt der Tag, wird es übernommen
+        match (result, notation.tag("Result")) {
+            (Some(outcome), Some(tag)) if outcome.token() != tag => {
+                return Err(NotationError::Result(format!(
+                    "movetext ends with {} but the Result tag is {tag}",
+                    outcome.token()
+                )))
+            }
+            (Some(outcome), None) => notation.set_tag("Result", outcome.token()),
+            _ => {}
+        }
+        Ok(notation)
+    }
+
+    /*
+       Spielt alle Züge auf einem GameBoard nach und prüft dabei Brettgröße, Variante, die Legalität
+       jedes Zugs und ob das angegebene Ergebnis zum tatsächlichen Ausgang passt
+    */
+    pub fn replay(&self) -> Result<Replay, NotationError> {
+        let board_size = format!("{WIDTH}x{HEIGHT}");
+        match self.tag("Board") {
+            Some(board) if board != board_size => {
+                return Err(NotationError::Tag(format!(
+                    "board {board} is not supported, expected {board_size}"
+                )))
+            }
+            _ => {}
+        }
+        match self.tag("Variant") {
+            Some(variant) if variant != VARIANT => {
+                return Err(NotationError::Tag(format!(
+                    "variant {variant} is not supported, expected {VARIANT}"
+                )))
+            }
+            _ => {}
+        }
+        let computer_started = match self.tag("Computer") {
+            Some("Red") => true,
+            Some("Yellow") | None => false,
+            Some(computer) => {
+                return Err(NotationError::Tag(format!(
+                    "computer must be Red or Yellow, not {computer}"
+                )))
+            }
+        };
+
+        let moves = self
+            .moves
+            .iter()
+            .map(|notated| RecordedMove {
+                column: notated.column,
+                score: notated.score,
+            })
+            .collect::<Vec<_>>();
+        let replay = replay_moves(&moves, computer_started)?;
+        match self.tag("Result") {
+            Some(result) if result != replay.outcome.token() => {
+                Err(NotationError::Result(format!(
+                    "Result tag is {result} but the moves end with {}",
+                    replay.outcome.token()
+                )))
+            }
+            _ => Ok(replay),
+        }
+    }
+
+    /*
+       Überführt eine nachgespielte, beendete Partie in einen Eintrag des Verlaufs. Der Mensch ist player,
+       bei Partien zwischen zwei Menschen ist Rot player und Gelb opponent
+    */
+    pub fn to_record(&self, id: &str) -> Result<GameRecord, NotationError> {
+        let replay = self.replay()?;
+        if replay.outcome == Outcome::Ongoing {
+            return Err(NotationError::Result(
+                "the game is not finished".to_string(),
+            ));
+        }
+        let red = self.tag("Red").unwrap_or("?").to_string();
+        let yellow = self.tag("Yellow").unwrap_or("?").to_string();
+        let (player, opponent, computer_started, red_is_player) = match self.tag("Computer") {
+            Some("Red") => (yellow, None, true, false),
+            Some(_) => (red, None, false, true),
+            None => (red, Some(yellow), false, true),
+        };
+        let result = match (replay.outcome, red_is_player) {
+            (Outcome::Draw, _) => "draw",
+            (Outcome::RedWins, true) | (Outcome::YellowWins, false) => "player_wins",
+            _ => "opponent_wins",
+        };
+        let level = match self.tag("Level") {
+            Some(level) => Some(
+                level
+                    .parse()
+                    .map_err(|_| NotationError::Tag(format!("invalid level {level}")))?,
+            ),
+            None => None,
+        };
+        let seed = match self.tag("Seed") {
+            Some(seed) => seed
+                .parse()
+                .map_err(|_| NotationError::Tag(format!("invalid seed {seed}")))?,
+            None => 0,
+        };
+        let takebacks = match self.tag("Takebacks") {
+            Some(takebacks) => takebacks
+                .parse()
+                .map_err(|_| NotationError::Tag(format!("invalid takebacks {takebacks}")))?,
+            None => 0,
+        };
+        let started_at = match self.tag("Date") {
+            Some(date) => parse_timestamp(date, self.tag("Time"))?,
+            None => unix_time(),
+        };
+
+        Ok(GameRecord {
+            id: id.to_string(),
+            player,
+            opponent,
+            level,
+            adaptive: false,
+            computer_started,
+            seed,
+            result: result.to_string(),
+            started_at,
+            // das Ende der Partie ist nicht notiert
+            finished_at: started_at,
+            takebacks,
+            moves: self
+                .moves
+                .iter()
+                .map(|notated| RecordedMove {
+                    column: notated.column,
+                    score: notated.score,
+                })
+                .collect(),
+        })
+    }
+}
+
+impl fmt::Display for GameNotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Tags stehen jeweils auf einer Zeile, Zeilenumbrüche im Wert werden zu Leerzeichen
+        for (name, value) in &self.tags {
+            writeln!(
+                f,
+                "[{name} \"{}\"]",
+                value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace(['\r', '\n'], " ")
+            )?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = Vec::new();
+        for (ply, notated) in self.moves.iter().enumerate() {
+            let column = (b'a' + notated.column) as char;
+            if ply % 2 == 0 {
+                tokens.push(format!("{}. {column}", ply / 2 + 1));
+            } else {
+                tokens.push(column.to_string());
+            }
+            let mut comment = Vec::new();
+            if let Some(score) = notated.score {
+                comment.push(format!("[%score {score}]"));
+            }
+            if let Some(text) = &notated.comment {
+                comment.push(text.replace('{', "(").replace('}', ")"));
+            }
+            if !comment.is_empty() {
+                tokens.push(format!("{{{}}}", comment.join(" ")));
+            }
+        }
+        tokens.push(self.tag("Result").unwrap_or("*").to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                writeln!(f, "{line}")?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{line}")
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn notation(
+    id: &str,
+    player: &str,
+    opponent: Option<&str>,
+    level: Option<u8>,
+    computer_started: bool,
+    seed: u64,
+    started_at: u64,
+    takebacks: u8,
+    moves: &[RecordedMove],
+    outcome: Outcome,
+) -> GameNotation {
+    let opponent = opponent.unwrap_or(ENGINE_NAME);
+    let (red, yellow) = if computer_started {
+        (opponent, player)
+    } else {
+        (player, opponent)
+    };
+    let (date, time) = format_timestamp(started_at);
+
+    let mut notation = GameNotation::default();
+    notation.set_tag("Event", "Connect Four");
+    notation.set_tag("Site", "connect4_server");
+    notation.set_tag("Date", date);
+    notation.set_tag("Time", time);
+    notation.set_tag("Red", red);
+    notation.set_tag("Yellow", yellow);
+    notation.set_tag("Result", outcome.token());
+    notation.set_tag("Variant", VARIANT);
+    notation.set_tag("Board", format!("{WIDTH}x{HEIGHT}"));
+    notation.set_tag("Game", id);
+    if opponent == ENGINE_NAME {
+        notation.set_tag("Computer", if computer_started { "Red" } else { "Yellow" });
+    }
+    if let Some(level) = level {
+        notation.set_tag("Level", level);
+    }
+    notation.set_tag("Seed", seed);
+    if takebacks > 0 {
+        notation.set_tag("Takebacks", takebacks);
+    }
+    notation.moves = moves
+        .iter()
+        .map(|recorded| NotatedMove {
+            column: recorded.column,
+            score: recorded.score,
+            comment: None,
+        })
+        .collect();
+    notation
+}
+
+// Rot erhält die Steine des Computers, wenn dieser begonnen hat, damit das Brett zu /next_move passt
+fn replay_moves(moves: &[RecordedMove], computer_started: bool) -> Result<Replay, NotationError> {
+    let (red, yellow) = if computer_started {
+        (COMPUTER_PLAYER, USER_PLAYER)
+    } else {
+        (USER_PLAYER, COMPUTER_PLAYER)
+    };
+    let mut board = GameBoard::new();
+    let mut outcome = Outcome::Ongoing;
+    for (index, recorded) in moves.iter().enumerate() {
+        let ply = index + 1;
+        if outcome != Outcome::Ongoing {
+            return Err(NotationError::IllegalMove(
+                ply,
+                "the game is already over".to_string(),
+            ));
+        }
+        if recorded.column as usize >= WIDTH {
+            return Err(NotationError::IllegalMove(
+                ply,
+                format!("invalid column {}", recorded.column),
+            ));
+        }
+        let chip = if index % 2 == 0 { red } else { yellow };
+        board
+            .drop_chip(recorded.column as usize, chip)
+            .ok_or_else(|| {
+                NotationError::IllegalMove(
+                    ply,
+                    format!("column {} is full", (b'a' + recorded.column) as char),
+                )
+            })?;
+
+        if check_for_row(&board.grid, chip, 4).0 {
+            outcome = if chip == red {
+                Outcome::RedWins
+            } else {
+                Outcome::YellowWins
+            };
+        } else if available_fields(&board).is_empty() {
+            outcome = Outcome::Draw;
+        }
+    }
+    Ok(Replay { board, outcome })
+}
+
+fn parse_tag(line: &str) -> Result<(String, String), String> {
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|line| line.strip_suffix(']'))
+        .ok_or_else(|| format!("invalid tag line '{line}'"))?;
+    let (name, value) = inner
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| format!("tag '{inner}' has no value"))?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("invalid tag name '{name}'"));
+    }
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(|| format!("value of tag {name} must be quoted"))?;
+
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(
+                chars
+                    .next()
+                    .ok_or_else(|| format!("value of tag {name} ends with '\\'"))?,
+            ),
+            '"' => return Err(format!("unescaped quote in tag {name}")),
+            c => unescaped.push(c),
+        }
+    }
+    Ok((name.to_string(), unescaped))
+}
+
+// Kommentar zum letzten Zug, [%score N] wird als Bewertung der Engine gelesen
+fn attach_comment(moves: &mut [NotatedMove], text: &str) -> Result<(), String> {
+    let notated = moves
+        .last_mut()
+        .ok_or_else(|| "comment before the first move".to_string())?;
+    let mut text = text.trim().to_string();
+    if let Some(start) = text.find("[%score ") {
+        let end = text[start..]
+            .find(']')
+            .map(|end| start + end)
+            .ok_or_else(|| "unterminated [%score] annotation".to_string())?;
+        let score = text[start + "[%score ".len()..end].trim();
+        notated.score = Some(
+            score
+                .parse()
+                .map_err(|_| format!("invalid score '{score}'"))?,
+        );
+        text.replace_range(start..=end, "");
+        text = text.trim().to_string();
+    }
+    if !text.is_empty() {
+        notated.comment = Some(match notated.comment.take() {
+            Some(comment) => format!("{comment} {text}"),
+            None => text,
+        });
+    }
+    Ok(())
+}
+
+fn is_move_number(token: &str) -> bool {
+    token
+        .strip_suffix('.')
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn parse_column(token: &str) -> Option<u8> {
+    match token.as_bytes() {
+        [column @ b'a'..=b'z'] if ((column - b'a') as usize) < WIDTH => Some(column - b'a'),
+        _ => None,
+    }
+}
+
+// Unix-Zeit als Datum "JJJJ.MM.TT" und Uhrzeit "HH:MM:SS" in UTC
+fn format_timestamp(timestamp: u64) -> (String, String) {
+    let days = (timestamp / 86_400) as i64;
+    let seconds = timestamp % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    (
+        format!("{year:04}.{month:02}.{day:02}"),
+        format!(
+            "{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        ),
+    )
+}
+
+fn parse_timestamp(date: &str, time: Option<&str>) -> Result<u64, NotationError> {
+    let invalid = || NotationError::Tag(format!("invalid date {date}"));
+    let parts = date
+        .split('.')
+        .map(|part| part.parse::<i64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let [year, month, day] = parts[..] else {
+        return Err(invalid());
+    };
+    if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+
+    let seconds = match time {
+        Some(time) => {
+            let invalid = || NotationError::Tag(format!("invalid time {time}"));
+            let parts = time
+                .split(':')
+                .map(|part| part.parse::<u64>().map_err(|_| invalid()))
+                .collect::<Result<Vec<_>, _>>()?;
+            match parts[..] {
+                [hours, minutes, seconds] if hours < 24 && minutes < 60 && seconds < 60 => {
+                    hours * 3600 + minutes * 60 + seconds
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        None => 0,
+    };
+    Ok(days_from_civil(year, month, day) as u64 * 86_400 + seconds)
+}
+
+// Umrechnung zwischen Tagen seit 1970-01-01 und dem gregorianischen Kalender (nach Howard Hinnant)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -0,0 +1,234 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tokio::sync::oneshot;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PoolError {
+    // alle Threads sind beschäftigt und die Warteschlange ist voll
+    Saturated,
+    Timeout(Duration),
+    // die Aufgabe ist abgestürzt oder der Pool wurde beendet
+    Closed,
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::Saturated => write!(f, "server is busy, please retry later"),
+            PoolError::Timeout(timeout) => {
+                write!(f, "search did not finish within {} ms", timeout.as_millis())
+            }
+            PoolError::Closed => write!(f, "search failed"),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {}
+
+/*
+   Zustand der Threads des Pools
+    - alive: laufende Threads, sie enden erst, wenn der Pool beendet wird
+    - busy: Threads, die gerade eine Aufgabe ausführen
+    - last_finished: Ende der letzten Aufgabe bzw. Start des Pools
+*/
+struct Workers {
+    alive: AtomicUsize,
+    busy: AtomicUsize,
+    last_finished: Mutex<Instant>,
+}
+
+/*
+   Momentaufnahme für /readyz. Ein ausgelasteter Pool ist nicht krank, erst wenn alle Threads beschäftigt sind
+   und seit stalled_after keine Aufgabe fertig geworden ist, hängt er
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolStatus {
+    pub workers: usize,
+    pub alive: usize,
+    pub busy: usize,
+    pub since_last_finished: Duration,
+    pub stalled_after: Duration,
+}
+
+impl PoolStatus {
+    pub fn is_live(&self) -> bool {
+        self.alive > 0 && (self.busy < self.alive || self.since_last_finished < self.stalled_after)
+    }
+}
+
+/*
+   Fester Pool an Threads für die rechenintensive Suche der Engine, damit die Worker von actix frei bleiben
+    - workers: Anzahl der Threads, die gleichzeitig rechnen
+    - queue_limit: Anzahl an Aufgaben, die zusätzlich warten dürfen, weitere werden abgelehnt
+    - timeout: maximale Wartezeit eines Aufrufers auf sein Ergebnis
+*/
+pub struct ComputePool {
+    sender: SyncSender<Job>,
+    timeout: Duration,
+    // längster Timeout, mit dem bisher eine Aufgabe gestartet wurde, in Millisekunden
+    longest_timeout_ms: AtomicU64,
+    workers: usize,
+    state: Arc<Workers>,
+}
+
+impl ComputePool {
+    pub fn new(workers: usize, queue_limit: usize, timeout: Duration) -> ComputePool {
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue_limit);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = workers.max(1);
+        let state = Arc::new(Workers {
+            alive: AtomicUsize::new(workers),
+            busy: AtomicUsize::new(0),
+            last_finished: Mutex::new(Instant::now()),
+        });
+
+        for worker in 0..workers {
+            let receiver = receiver.clone();
+            let state = state.clone();
+            thread::Builder::new()
+                .name(format!("compute-{worker}"))
+                .spawn(move || work(&receiver, &state))
+                .expect("failed to spawn compute thread");
+        }
+
+        ComputePool {
+            sender,
+            timeout,
+            longest_timeout_ms: AtomicU64::new(timeout.as_millis() as u64),
+            workers,
+            state,
+        }
+    }
+
+    /*
+       Zustand der Threads. Eine abgebrochene Suche endet spätestens ein Timeout nach dem Abbruch, wird
+       innerhalb des längsten Timeouts und eines weiteren Timeouts keine Aufgabe fertig, gilt der Pool als hängend
+    */
+    pub fn status(&self, now: Instant) -> PoolStatus {
+        let last_finished = *self.state.last_finished.lock().unwrap();
+        PoolStatus {
+            workers: self.workers,
+            alive: self.state.alive.load(Ordering::SeqCst),
+            busy: self.state.busy.load(Ordering::SeqCst),
+            since_last_finished: now.saturating_duration_since(last_finished),
+            stalled_after: Duration::from_millis(self.longest_timeout_ms.load(Ordering::SeqCst))
+                + self.timeout,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    // reiht eine Aufgabe ein, ohne zu blockieren; das Ergebnis kommt über den zurückgegebenen Kanal
+    pub fn submit<T, F>(&self, job: F) -> Result<oneshot::Receiver<T>, PoolError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let job: Job = Box::new(move || {
+            // der Aufrufer wartet eventuell nicht mehr auf das Ergebnis
+            let _ = result_sender.send(job());
+        });
+
+        match self.sender.try_send(job) {
+            Ok(()) => Ok(result_receiver),
+            Err(TrySendError::Full(_)) => Err(PoolError::Saturated),
+            Err(TrySendError::Disconnected(_)) => Err(PoolError::Closed),
+        }
+    }
+
+    // führt die Aufgabe im Pool aus und wartet höchstens bis zum Timeout auf das Ergebnis
+    pub async fn run<T, F>(&self, job: F) -> Result<T, PoolError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let result_receiver = self.submit(job)?;
+        match tokio::time::timeout(self.timeout, result_receiver).await {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(_)) => Err(PoolError::Closed),
+            Err(_) => Err(PoolError::Timeout(self.timeout)),
+        }
+    }
+
+    /*
+       Wie run, nach dem Timeout wird die Aufgabe aber über cancel abgebrochen und auf ihr bis dahin
+       bestes Ergebnis gewartet. Erst wenn auch danach kein Ergebnis kommt, schlägt der Aufruf fehl.
+       Wartet die Aufgabe beim Timeout noch in der Warteschlange, wird sie nie ausgeführt, ein Timeout
+       bedeutet dann, dass die Aufgabe nichts verändert hat
+    */
+    pub async fn run_cancellable<T, F, C>(&self, job: F, cancel: C) -> Result<T, PoolError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+        C: FnOnce(),
+    {
+        self.run_cancellable_for(self.timeout, job, cancel).await
+    }
+
+    // wie run_cancellable, mit eigenem Timeout für Aufgaben, die länger rechnen dürfen (z.B. Nachbesprechungen)
+    pub async fn run_cancellable_for<T, F, C>(
+        &self,
+        timeout: Duration,
+        job: F,
+        cancel: C,
+    ) -> Result<T, PoolError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+        C: FnOnce(),
+    {
+        self.longest_timeout_ms
+            .fetch_max(timeout.as_millis() as u64, Ordering::SeqCst);
+        // wer zuerst kommt, entscheidet: der Thread startet die Aufgabe oder der Aufrufer gibt sie auf
+        let claimed = Arc::new(AtomicBool::new(false));
+        let started = claimed.clone();
+        let mut result_receiver =
+            self.submit(move || (!started.swap(true, Ordering::SeqCst)).then(job))?;
+        if let Ok(result) = tokio::time::timeout(timeout, &mut result_receiver).await {
+            return result.ok().flatten().ok_or(PoolError::Closed);
+        }
+        if !claimed.swap(true, Ordering::SeqCst) {
+            return Err(PoolError::Timeout(timeout));
+        }
+
+        cancel();
+        match tokio::time::timeout(self.timeout, result_receiver).await {
+            Ok(Ok(Some(result))) => Ok(result),
+            Ok(_) => Err(PoolError::Closed),
+            Err(_) => Err(PoolError::Timeout(self.timeout)),
+        }
+    }
+}
+
+fn work(receiver: &Mutex<Receiver<Job>>, state: &Workers) {
+    loop {
+        // der Lock wird nur für das Abholen der nächsten Aufgabe gehalten
+        let job = receiver.lock().unwrap().recv();
+        match job {
+            // eine abgestürzte Suche soll nicht den Thread des Pools beenden
+            Ok(job) => {
+                state.busy.fetch_add(1, Ordering::SeqCst);
+                let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                *state.last_finished.lock().unwrap() = Instant::now();
+                state.busy.fetch_sub(1, Ordering::SeqCst);
+            }
+            Err(_) => {
+                state.alive.fetch_sub(1, Ordering::SeqCst);
+                return;
+            }
+        }
+    }
+}
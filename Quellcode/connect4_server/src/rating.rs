@@ -0,0 +1,94 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use serde::{Deserialize, Serialize};
+
+use crate::connect4ai::NextMoveResult;
+use crate::ladder::{get_level, LEVELS};
+
+// Startwertung neuer Spieler, entspricht etwa der Stufe Easy
+pub const INITIAL_RATING: f64 = 900.0;
+
+// Anzahl an Spielen, in denen sich das Rating schneller anpasst
+const PROVISIONAL_GAMES: u32 = 10;
+const PROVISIONAL_K_FACTOR: f64 = 64.0;
+const K_FACTOR: f64 = 24.0;
+
+/*
+   Elo-Rating eines Spielers, das nach jedem beendeten Spiel gegen die Engine angepasst wird
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlayerRating {
+    pub rating: f64,
+    pub games: u32,
+}
+
+impl Default for PlayerRating {
+    fn default() -> Self {
+        PlayerRating {
+            rating: INITIAL_RATING,
+            games: 0,
+        }
+    }
+}
+
+impl PlayerRating {
+    /*
+       Passt das Rating nach einem Spiel gegen die übergebene Stufe an
+       score: 1 bei Sieg des Spielers, 0.5 bei Unentschieden, 0 bei Niederlage
+    */
+    pub fn update(&mut self, level: u8, score: f64) {
+        self.update_assisted(level, score, 0);
+    }
+
+    /*
+       Wie update, für Spiele mit zurückgenommenen Zügen: ein Gewinn an Rating wird durch
+       1 + takebacks geteilt, ein Verlust zählt voll, damit Zugrücknahmen das Rating nicht aufblähen
+    */
+    pub fn update_assisted(&mut self, level: u8, score: f64, takebacks: u8) {
+        let Ok(level) = get_level(level) else {
+            return;
+        };
+
+        let k_factor = if self.games < PROVISIONAL_GAMES {
+            PROVISIONAL_K_FACTOR
+        } else {
+            K_FACTOR
+        };
+
+        let change = k_factor * (score - expected_score(self.rating, level.rating as f64));
+        self.rating += if change > 0.0 {
+            change / (1.0 + takebacks as f64)
+        } else {
+            change
+        };
+        self.games += 1;
+    }
+
+    // wählt die Stufe, gegen die der Spieler etwa die Hälfte seiner Spiele gewinnt
+    pub fn adaptive_level(&self) -> u8 {
+        LEVELS
+            .iter()
+            .min_by(|a, b| {
+                let distance_a = (a.rating as f64 - self.rating).abs();
+                let distance_b = (b.rating as f64 - self.rating).abs();
+                distance_a.total_cmp(&distance_b)
+            })
+            .map(|level| level.level)
+            .unwrap()
+    }
+}
+
+// erwartete Punktzahl eines Spielers mit Rating a gegen einen Gegner mit Rating b
+pub fn expected_score(a: f64, b: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((b - a) / 400.0))
+}
+
+// Punkte des Spielers für ein beendetes Spiel, None wenn das Spiel noch läuft
+pub fn player_score(result: &NextMoveResult) -> Option<f64> {
+    match result {
+        NextMoveResult::PlayerWins => Some(1.0),
+        NextMoveResult::Draw => Some(0.5),
+        NextMoveResult::ComputerWins => Some(0.0),
+        NextMoveResult::NextMove | NextMoveResult::None => None,
+    }
+}
@@ -0,0 +1,236 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::connect4ai::{
+    analyse_moves, check_for_row, moves_to_loss, moves_to_win, Difficulty, GameBoard,
+    SearchOptions, COMPUTER_PLAYER, THREAT_L4_SCORE, USER_PLAYER, WIDTH,
+};
+use crate::ladder::HARD_LEVEL;
+use crate::notation::Side;
+use crate::skill::is_blunder;
+
+// Stufe, mit der die Engine ein Spiel nachbespricht, wenn keine Stufe angegeben ist
+pub const DEFAULT_REVIEW_LEVEL: u8 = HARD_LEVEL;
+
+// ab diesem Score-Verlust gilt ein Zug als Ungenauigkeit bzw. Fehler, Patzer wie bei skill::is_blunder
+pub const INACCURACY_SCORE_LOSS: i64 = THREAT_L4_SCORE / 2;
+pub const MISTAKE_SCORE_LOSS: i64 = 2 * THREAT_L4_SCORE;
+
+#[derive(Debug, PartialEq)]
+pub enum ReviewError {
+    // Halbzug (ab 1) und Spalte
+    InvalidColumn(usize, u8),
+    ColumnFull(usize, u8),
+    // Halbzug, der nach dem Ende des Spiels folgt
+    GameOver(usize),
+}
+
+impl fmt::Display for ReviewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReviewError::InvalidColumn(ply, column) => write!(
+                f,
+                "move {ply}: invalid column {column}, expected 0 to {}",
+                WIDTH - 1
+            ),
+            ReviewError::ColumnFull(ply, column) => {
+                write!(f, "move {ply}: column {column} is full")
+            }
+            ReviewError::GameOver(ply) => write!(f, "move {ply}: the game is already over"),
+        }
+    }
+}
+
+impl std::error::Error for ReviewError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Classification {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+/*
+   Einordnung eines Zugs anhand des Scores des besten Zugs und des gespielten Zugs. Unabhängig vom
+   Score-Verlust ist ein Zug ein Patzer, wenn er eine nicht verlorene Stellung in eine erzwungene Niederlage
+   verwandelt oder einen erzwungenen Sieg aus der Hand gibt
+*/
+pub fn classify(best_score: i64, score: i64) -> Classification {
+    let throws_away_win = moves_to_win(best_score).is_some() && moves_to_win(score).is_none();
+    let walks_into_loss = moves_to_loss(best_score).is_none() && moves_to_loss(score).is_some();
+    let score_loss = best_score - score;
+
+    if score >= best_score {
+        Classification::Best
+    } else if throws_away_win || walks_into_loss || is_blunder(best_score, score) {
+        Classification::Blunder
+    } else if score_loss >= MISTAKE_SCORE_LOSS {
+        Classification::Mistake
+    } else if score_loss >= INACCURACY_SCORE_LOSS {
+        Classification::Inaccuracy
+    } else {
+        Classification::Good
+    }
+}
+
+/*
+   Nachbesprechung eines Zugs, alle Scores aus Sicht der ziehenden Seite
+    - best_column / best_score: bester Zug laut Engine und dessen Score
+    - pv: Hauptvariante des besten Zugs, nur bei Ungenauigkeiten, Fehlern und Patzern
+*/
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MoveReview {
+    pub ply: usize,
+    pub side: Side,
+    pub column: u8,
+    pub classification: Classification,
+    pub score: i64,
+    pub best_column: u8,
+    pub best_score: i64,
+    pub score_loss: i64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pv: Vec<u8>,
+    pub depth: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SideSummary {
+    pub side: Side,
+    pub best: usize,
+    pub good: usize,
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+}
+
+impl SideSummary {
+    fn new(side: Side, moves: &[MoveReview]) -> SideSummary {
+        let count = |classification| {
+            moves
+                .iter()
+                .filter(|review| review.side == side && review.classification == classification)
+                .count()
+        };
+        SideSummary {
+            side,
+            best: count(Classification::Best),
+            good: count(Classification::Good),
+            inaccuracies: count(Classification::Inaccuracy),
+            mistakes: count(Classification::Mistake),
+            blunders: count(Classification::Blunder),
+        }
+    }
+}
+
+/*
+   Ergebnis der Nachbesprechung
+    - complete: false, wenn nicht jeder Zug bewertet wurde: nach einem Abbruch der Suche (z.B. Timeout) fehlen
+      die letzten Züge, Züge ohne Score der Engine fehlen einzeln, statt als bester Zug zu gelten
+*/
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GameReview {
+    pub level: Option<u8>,
+    pub complete: bool,
+    pub moves: Vec<MoveReview>,
+    pub summary: [SideSummary; 2],
+}
+
+/*
+   Spielt die Züge nach (Spalten 0-6, Rot beginnt) und bewertet vor jedem Zug alle möglichen Züge mit der Engine.
+   Die Engine sucht immer für den Computer, deshalb werden die Steine getauscht, wenn Rot am Zug ist.
+   Jede Stellung wird mit der festen Tiefe der Stufe bewertet, ein Zeitlimit der Stufe gilt für einen einzelnen
+   Zug und würde sich über alle Stellungen des Spiels aufsummieren. Ungültige Züge werden vor der ersten Suche
+   abgelehnt
+*/
+pub fn review_game(
+    moves: &[u8],
+    difficulty: &Difficulty,
+    options: &SearchOptions,
+) -> Result<GameReview, ReviewError> {
+    validate_moves(moves)?;
+
+    let difficulty = &difficulty.fixed_depth();
+    let mut game_board = GameBoard::new();
+    let mut reviews = Vec::new();
+    let mut complete = true;
+    for (index, &column) in moves.iter().enumerate() {
+        let (side, chip) = if index % 2 == 0 {
+            (Side::Red, USER_PLAYER)
+        } else {
+            (Side::Yellow, COMPUTER_PLAYER)
+        };
+        let position = if chip == COMPUTER_PLAYER {
+            game_board.clone()
+        } else {
+            game_board.swap_players()
+        };
+
+        let analysis = analyse_moves(&position, side == Side::Red, difficulty, options);
+        let scored = analysis.and_then(|analysis| Some((analysis.score_of(column)?, analysis)));
+        match scored {
+            Some((score, analysis)) => {
+                let classification = classify(analysis.best_score, score);
+                let bad_move =
+                    !matches!(classification, Classification::Best | Classification::Good);
+                reviews.push(MoveReview {
+                    ply: index + 1,
+                    side,
+                    column,
+                    classification,
+                    score,
+                    best_column: analysis.best_column,
+                    best_score: analysis.best_score,
+                    score_loss: analysis.best_score - score,
+                    pv: if bad_move { analysis.pv } else { Vec::new() },
+                    depth: analysis.depth,
+                });
+            }
+            None if options.cancel.is_cancelled() => {
+                complete = false;
+                break;
+            }
+            None => complete = false,
+        }
+        game_board.drop_chip(column as usize, chip);
+    }
+
+    Ok(GameReview {
+        level: difficulty.level,
+        complete,
+        summary: [
+            SideSummary::new(Side::Red, &reviews),
+            SideSummary::new(Side::Yellow, &reviews),
+        ],
+        moves: reviews,
+    })
+}
+
+fn validate_moves(moves: &[u8]) -> Result<(), ReviewError> {
+    let mut game_board = GameBoard::new();
+    let mut over = false;
+    for (index, &column) in moves.iter().enumerate() {
+        let ply = index + 1;
+        if over {
+            return Err(ReviewError::GameOver(ply));
+        }
+        if column as usize >= WIDTH {
+            return Err(ReviewError::InvalidColumn(ply, column));
+        }
+        let chip = if index % 2 == 0 {
+            USER_PLAYER
+        } else {
+            COMPUTER_PLAYER
+        };
+        if game_board.drop_chip(column as usize, chip).is_none() {
+            return Err(ReviewError::ColumnFull(ply, column));
+        }
+        over = check_for_row(&game_board.grid, chip, 4).0;
+    }
+    Ok(())
+}
@@ -0,0 +1,929 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::cancellation::{CancelToken, SearchRegistry};
+use crate::connect4ai::{
+    available_fields, check_for_row, moves_to_loss, moves_to_win, next_move_with, ponder,
+    rng_from_seed, Difficulty, GameBoard, NextMoveResult, SearchOptions, USER_PLAYER, WIDTH,
+};
+use crate::history::{GameHistory, GameRecord};
+use crate::ladder::{get_level, DifficultyError, TakebackLimits};
+use crate::rating::{player_score, PlayerRating};
+use crate::transposition::TranspositionTable;
+
+// Stände, die ein langsamer Zuschauer verpassen darf, bevor er den aktuellen Stand erhält
+const UPDATE_CAPACITY: usize = 16;
+// Namen sind Schlüssel der Ratings, werden im Verlauf gespeichert und an Gegner und Zuschauer gesendet
+pub const MAX_PLAYER_NAME_LENGTH: usize = 32;
+
+/*
+   Ein zustandsbehaftetes Spiel eines Spielers gegen die Engine
+    - moves: Spalten aller bisherigen Züge in Reihenfolge
+    - adaptive: ob die Stufe anhand des Spieler-Ratings gewählt wurde
+    - blunders: bisherige Patzer der Engine, begrenzt durch das Patzer-Budget der Stufe
+    - seed: Grundlage aller zufälligen Entscheidungen der Engine, damit das Spiel reproduzierbar ist
+    - loss_in / win_in: Anzahl der Züge bis zum erzwungenen Sieg des Spielers bzw. der Engine
+    - ponder: ob die Engine weiterrechnet, während der Spieler überlegt
+    - scores: Score der Engine nach jedem ihrer Züge, None bei Zügen des Spielers
+    - started_at / finished_at: Unix-Zeit in Sekunden
+    - takebacks / takeback_limit: bisher zurückgenommene und insgesamt erlaubte Zugrücknahmen
+    - reproducible: ob der Seed vorgegeben wurde, die Engine rechnet dann mit einem Thread und fester Tiefe
+    - rating_change: Änderung des Spieler-Ratings durch das Ergebnis, None solange das Spiel läuft
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Game {
+    pub id: String,
+    pub player: String,
+    pub board: GameBoard,
+    pub computer_started: bool,
+    pub level: u8,
+    pub adaptive: bool,
+    pub blunders: u8,
+    pub seed: u64,
+    pub moves: Vec<u8>,
+    pub score: i64,
+    pub loss_in: Option<u8>,
+    pub win_in: Option<u8>,
+    pub result: NextMoveResult,
+    pub ponder: bool,
+    // Snapshots älterer Versionen enthalten diese Felder noch nicht
+    #[serde(default)]
+    pub scores: Vec<Option<i64>>,
+    #[serde(default)]
+    pub started_at: u64,
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    #[serde(default)]
+    pub takebacks: u8,
+    // ohne Angabe setzt SessionStore::restore die Grenze der Stufe ein
+    #[serde(default = "unknown_takeback_limit")]
+    pub takeback_limit: u8,
+    #[serde(default)]
+    pub reproducible: bool,
+    #[serde(default)]
+    pub rating_change: Option<f64>,
+}
+
+// Platzhalter für Snapshots ohne takeback_limit, keine Stufe erlaubt so viele Zugrücknahmen
+const UNKNOWN_TAKEBACK_LIMIT: u8 = u8::MAX;
+
+fn unknown_takeback_limit() -> u8 {
+    UNKNOWN_TAKEBACK_LIMIT
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GameError {
+    NotFound(String),
+    GameOver,
+    InvalidColumn(u8),
+    ColumnFull(u8),
+    Difficulty(DifficultyError),
+    NothingToUndo,
+    // alle erlaubten Zugrücknahmen wurden bereits genutzt
+    NoTakebacksLeft(u8),
+    // die Engine rechnet noch an ihrer Antwort auf den letzten Zug
+    EngineThinking,
+    InvalidName,
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::NotFound(id) => write!(f, "game {id} not found"),
+            GameError::GameOver => write!(f, "game is already over"),
+            GameError::InvalidColumn(column) => {
+                write!(f, "invalid column {column}, expected 0 to {}", WIDTH - 1)
+            }
+            GameError::ColumnFull(column) => write!(f, "column {column} is full"),
+            GameError::Difficulty(error) => error.fmt(f),
+            GameError::NothingToUndo => write!(f, "there is no move to take back"),
+            GameError::NoTakebacksLeft(limit) => {
+                write!(f, "no takebacks left, {limit} allowed per game")
+            }
+            GameError::EngineThinking => write!(f, "the engine is still thinking about its move"),
+            GameError::InvalidName => write!(
+                f,
+                "player name must have 1 to {MAX_PLAYER_NAME_LENGTH} characters"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+impl From<DifficultyError> for GameError {
+    fn from(error: DifficultyError) -> Self {
+        GameError::Difficulty(error)
+    }
+}
+
+impl Game {
+    pub fn is_over(&self) -> bool {
+        player_score(&self.result).is_some()
+    }
+
+    // führt den Zug des Nutzers in der übergebenen Spalte aus
+    fn play_user_move(&mut self, column: u8) -> Result<(), GameError> {
+        if self.is_over() {
+            return Err(GameError::GameOver);
+        }
+        if column as usize >= WIDTH {
+            return Err(GameError::InvalidColumn(column));
+        }
+
+        self.board
+            .drop_chip(column as usize, USER_PLAYER)
+            .ok_or(GameError::ColumnFull(column))?;
+        self.moves.push(column);
+        self.scores.push(None);
+
+        if check_for_row(&self.board.grid, USER_PLAYER, 4).0 {
+            self.result = NextMoveResult::PlayerWins;
+        } else if available_fields(&self.board).is_empty() {
+            self.result = NextMoveResult::Draw;
+        }
+        self.finish_if_over();
+        Ok(())
+    }
+
+    // lässt die Engine auf der Stufe des Spiels antworten
+    fn play_engine_move(&mut self, options: &SearchOptions) -> Result<(), GameError> {
+        let mut difficulty = Difficulty::from_level(self.level)?;
+        let mut options = options.clone();
+        if self.reproducible {
+            difficulty = difficulty.fixed_depth();
+            options.threads = 1;
+        }
+        // jeder Zug erhält einen eigenen, aus dem Seed des Spiels abgeleiteten Zufallsgenerator
+        let mut rng = rng_from_seed(Some(self.seed.wrapping_add(self.moves.len() as u64)));
+        let (field, score, result, blunder) = next_move_with(
+            &mut self.board,
+            self.computer_started,
+            &difficulty,
+            self.blunders,
+            &mut rng,
+            &options,
+        );
+
+        // next_move_with setzt den Stein des Computers bereits auf das übergebene Brett
+        if let Some(field) = field {
+            self.moves.push(field.x);
+            self.scores.push(Some(score));
+        }
+        if blunder {
+            self.blunders += 1;
+        }
+        self.score = score;
+        self.loss_in = moves_to_loss(score);
+        self.win_in = moves_to_win(score);
+        self.result = match result {
+            NextMoveResult::NextMove if available_fields(&self.board).is_empty() => {
+                NextMoveResult::Draw
+            }
+            result => result,
+        };
+        self.finish_if_over();
+        Ok(())
+    }
+
+    /*
+       Nimmt den letzten Zug des Nutzers und die Antwort der Engine darauf zurück. Die Patzer der Engine
+       bleiben gezählt, damit Zugrücknahmen ihr Patzer-Budget nicht wieder auffüllen. Ein verlorenes Spiel
+       läuft danach weiter, gewonnene und unentschiedene Spiele bleiben beendet
+    */
+    fn undo(&mut self) -> Result<(), GameError> {
+        if self.is_over() && self.result != NextMoveResult::ComputerWins {
+            return Err(GameError::GameOver);
+        }
+        // beginnt der Computer, stehen die Züge des Nutzers an ungeraden Stellen
+        let last_user_move = (0..self.moves.len())
+            .rev()
+            .find(|index| (index % 2 == 1) == self.computer_started)
+            .ok_or(GameError::NothingToUndo)?;
+        if self.takebacks >= self.takeback_limit {
+            return Err(GameError::NoTakebacksLeft(self.takeback_limit));
+        }
+
+        for column in self.moves.drain(last_user_move..).rev() {
+            self.board.take_chip(column as usize);
+        }
+        self.scores.truncate(last_user_move);
+        self.takebacks += 1;
+        self.score = self
+            .scores
+            .iter()
+            .rev()
+            .find_map(|score| *score)
+            .unwrap_or(0);
+        self.loss_in = moves_to_loss(self.score);
+        self.win_in = moves_to_win(self.score);
+        self.result = NextMoveResult::NextMove;
+        self.finished_at = None;
+        Ok(())
+    }
+
+    fn finish_if_over(&mut self) {
+        if self.is_over() && self.finished_at.is_none() {
+            self.finished_at = Some(unix_time());
+        }
+    }
+}
+
+// gilt für Spiele gegen die Engine und zwischen zwei Menschen, Leerzeichen am Rand zählen nicht
+pub fn is_valid_player_name(player: &str) -> bool {
+    (1..=MAX_PLAYER_NAME_LENGTH).contains(&player.trim().chars().count())
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+// gespeicherte Spiele und Ratings, damit sie einen Neustart des Servers überstehen
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub games: Vec<Game>,
+    pub ratings: HashMap<String, PlayerRating>,
+}
+
+/*
+   Ein Spiel im Speicher
+    - transposition_table: Tabelle aus dem Vorrat fürs Pondering, bleibt über alle Züge des Spiels erhalten und
+      geht mit dem Spielende zurück
+    - pondering: Hintergrundsuche, während der Spieler überlegt
+    - last_activity: Zeitpunkt des letzten Zugs, inaktive Spiele werden nach einiger Zeit entfernt
+    - updates: Stand nach jedem Zug und jeder Zugrücknahme für Zuschauer
+    - thinking: ob die Engine gerade antwortet; der Lock des Spiels ist währenddessen frei, damit Abfragen
+      nicht auf die Suche warten, Züge und Zugrücknahmen werden aber abgelehnt
+    - removed: ob das Spiel beendet oder abgelaufen ist, eine noch laufende Antwort zählt dann nicht mehr
+    - recording: wird beim Speichern im Verlauf gehalten, das geschieht nach Freigabe des Spiels; eine
+      Zugrücknahme wartet darauf, bevor sie das Spiel wieder aus dem Verlauf entfernt
+*/
+struct GameEntry {
+    game: Mutex<Game>,
+    thinking: AtomicBool,
+    removed: AtomicBool,
+    recording: Mutex<()>,
+    transposition_table: Mutex<Option<PonderTable>>,
+    pondering: Mutex<Option<Pondering>>,
+    last_activity: Mutex<Instant>,
+    updates: broadcast::Sender<Game>,
+}
+
+/*
+   Eine laufende Hintergrundsuche. Sie wird ausgezählt, sobald sie angehalten wird oder ihr Thread von selbst
+   endet, je nachdem was zuerst passiert
+*/
+struct Pondering {
+    cancel: CancelToken,
+    finished: Arc<AtomicBool>,
+    pondering: Arc<AtomicUsize>,
+}
+
+impl Pondering {
+    fn finish(finished: &AtomicBool, pondering: &AtomicUsize) {
+        if !finished.swap(true, Ordering::SeqCst) {
+            pondering.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/*
+   Vorrat an Transpositionstabellen fürs Pondering. Eine Tabelle kann mehrere GB groß sein, deshalb gibt es
+   höchstens so viele, wie Spiele gleichzeitig pondern dürfen. Zurückgegebene Tabellen werden nicht geleert,
+   die Einträge gelten für jede Stellung unabhängig vom Spiel
+*/
+#[derive(Default)]
+struct PonderTables {
+    free: Mutex<Vec<Arc<TranspositionTable>>>,
+    allocated: AtomicUsize,
+}
+
+impl PonderTables {
+    // None, wenn bereits limit Tabellen vergeben sind
+    fn acquire(self: &Arc<Self>, limit: usize, entries: usize) -> Option<PonderTable> {
+        let reused = self.free.lock().unwrap().pop();
+        let table = match reused {
+            Some(table) => table,
+            None => {
+                if self.allocated.fetch_add(1, Ordering::SeqCst) >= limit {
+                    self.allocated.fetch_sub(1, Ordering::SeqCst);
+                    return None;
+                }
+                Arc::new(TranspositionTable::new(entries))
+            }
+        };
+        Some(PonderTable {
+            table,
+            tables: self.clone(),
+        })
+    }
+}
+
+// Tabelle eines Spiels, sie geht zurück in den Vorrat, sobald das Spiel endet oder entfernt wird
+struct PonderTable {
+    table: Arc<TranspositionTable>,
+    tables: Arc<PonderTables>,
+}
+
+impl Drop for PonderTable {
+    fn drop(&mut self) {
+        self.tables.free.lock().unwrap().push(self.table.clone());
+    }
+}
+
+impl GameEntry {
+    fn new(game: Game) -> GameEntry {
+        GameEntry {
+            transposition_table: Mutex::new(None),
+            game: Mutex::new(game),
+            thinking: AtomicBool::new(false),
+            removed: AtomicBool::new(false),
+            recording: Mutex::new(()),
+            pondering: Mutex::new(None),
+            last_activity: Mutex::new(Instant::now()),
+            updates: broadcast::channel(UPDATE_CAPACITY).0,
+        }
+    }
+
+    fn table(&self) -> Option<Arc<TranspositionTable>> {
+        self.transposition_table
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|table| table.table.clone())
+    }
+
+    fn publish(&self, game: &Game) {
+        // ohne Zuschauer gibt es niemanden zu benachrichtigen
+        let _ = self.updates.send(game.clone());
+    }
+
+    /*
+       Hält die Hintergrundsuche an, ohne auf ihren Thread zu warten, damit auch Aufrufer auf den Workern von
+       actix nicht blockieren. Der Thread bemerkt den Abbruch beim nächsten Knoten und endet von selbst
+    */
+    fn stop_pondering(&self) {
+        let pondering = self.pondering.lock().unwrap().take();
+        if let Some(pondering) = pondering {
+            pondering.cancel.cancel();
+            Pondering::finish(&pondering.finished, &pondering.pondering);
+        }
+    }
+}
+
+/*
+   Hält alle laufenden Spiele und die Ratings der Spieler im Speicher.
+   Jedes Spiel hat einen eigenen Lock, damit eine Suche nur das eigene Spiel blockiert.
+*/
+#[derive(Default)]
+pub struct SessionStore {
+    games: Mutex<HashMap<String, Arc<GameEntry>>>,
+    ratings: Mutex<HashMap<String, PlayerRating>>,
+    search_options: SearchOptions,
+    /*
+       laufende Züge der Engine unter der Id ihres Spiels, damit sie beim Beenden des Spiels, über
+       DELETE /searches/{id} und beim Herunterfahren abgebrochen werden können
+    */
+    searches: Arc<SearchRegistry>,
+    // Anzahl der Spiele, für die gerade im Hintergrund gerechnet wird
+    pondering: Arc<AtomicUsize>,
+    ponder_tables: Arc<PonderTables>,
+    // beendete Spiele werden hier gespeichert, sofern ein Verlauf konfiguriert ist
+    history: Option<Arc<GameHistory>>,
+    takeback_limits: TakebackLimits,
+    // Snapshot, der nicht geladen und nicht beiseitegelegt werden konnte; er wird nie überschrieben
+    kept_snapshot: Mutex<Option<PathBuf>>,
+}
+
+impl SessionStore {
+    pub fn new() -> SessionStore {
+        SessionStore::default()
+    }
+
+    // Sucheinstellungen (z.B. Anzahl der Threads) für alle Züge der Engine
+    pub fn with_search_options(search_options: SearchOptions) -> SessionStore {
+        SessionStore {
+            search_options,
+            ..SessionStore::default()
+        }
+    }
+
+    // Registry aller Suchen des Servers, in die sich auch die Züge der Engine eintragen
+    pub fn with_registry(self, searches: Arc<SearchRegistry>) -> SessionStore {
+        SessionStore { searches, ..self }
+    }
+
+    pub fn with_history(self, history: Arc<GameHistory>) -> SessionStore {
+        SessionStore {
+            history: Some(history),
+            ..self
+        }
+    }
+
+    // erlaubte Zugrücknahmen je Stufe, gilt für alle danach gestarteten Spiele
+    pub fn with_takeback_limits(self, takeback_limits: TakebackLimits) -> SessionStore {
+        SessionStore {
+            takeback_limits,
+            ..self
+        }
+    }
+
+    /*
+       Startet ein neues Spiel. Ohne Stufe wird die Stufe anhand des Ratings des Spielers gewählt,
+       sodass dieser etwa die Hälfte seiner Spiele gewinnt. Mit ponder rechnet die Engine weiter,
+       während der Spieler überlegt
+    */
+    pub fn create_game(
+        &self,
+        player: &str,
+        computer_started: bool,
+        level: Option<u8>,
+        seed: Option<u64>,
+        ponder: bool,
+        request_id: Option<&str>,
+    ) -> Result<Game, GameError> {
+        self.create_game_cancellable(
+            player,
+            computer_started,
+            level,
+            seed,
+            ponder,
+            request_id,
+            CancelToken::new(),
+        )
+    }
+
+    /*
+       Wie create_game, der erste Zug der Engine lässt sich über cancel abbrechen, sie spielt dann den besten
+       bisher gefundenen Zug
+    */
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_game_cancellable(
+        &self,
+        player: &str,
+        computer_started: bool,
+        level: Option<u8>,
+        seed: Option<u64>,
+        ponder: bool,
+        request_id: Option<&str>,
+        cancel: CancelToken,
+    ) -> Result<Game, GameError> {
+        if !is_valid_player_name(player) {
+            return Err(GameError::InvalidName);
+        }
+        let adaptive = level.is_none();
+        let level = match level {
+            Some(level) => get_level(level)?.level,
+            None => self.rating(player).adaptive_level(),
+        };
+
+        let game = Game {
+            id: format!("{:016x}", rand::random::<u64>()),
+            player: player.to_string(),
+            board: GameBoard::new(),
+            computer_started,
+            level,
+            adaptive,
+            blunders: 0,
+            seed: seed.unwrap_or_else(rand::random),
+            moves: Vec::new(),
+            score: 0,
+            loss_in: None,
+            win_in: None,
+            result: NextMoveResult::NextMove,
+            ponder,
+            scores: Vec::new(),
+            started_at: unix_time(),
+            finished_at: None,
+            takebacks: 0,
+            takeback_limit: self.takeback_limits.limit(level),
+            reproducible: seed.is_some(),
+            rating_change: None,
+        };
+
+        let id = game.id.clone();
+        let mut thinking = game.clone();
+        let entry = Arc::new(GameEntry::new(game));
+        /*
+           Das Spiel wird schon vor dem ersten Zug der Engine eingetragen und die Suche unter seiner Id registriert,
+           damit DELETE /games/{id} und DELETE /searches/{id} sie wie jeden anderen Zug abbrechen können
+        */
+        entry.thinking.store(computer_started, Ordering::SeqCst);
+        self.games.lock().unwrap().insert(id.clone(), entry.clone());
+        if computer_started {
+            self.searches.register(&id, cancel.clone());
+            let options = self.engine_options(&entry, &id, cancel.clone(), request_id);
+            let played = thinking.play_engine_move(&options);
+            self.searches.unregister(&id, &cancel);
+
+            let mut game = entry.game.lock().unwrap();
+            entry.thinking.store(false, Ordering::SeqCst);
+            if entry.removed.load(Ordering::SeqCst) {
+                return Err(GameError::NotFound(id));
+            }
+            if let Err(error) = played {
+                drop(game);
+                let _ = self.remove_game(&id);
+                return Err(error);
+            }
+            *game = thinking;
+            entry.publish(&game);
+        }
+
+        let game = entry.game.lock().unwrap();
+        self.start_pondering(&entry, &game);
+        Ok(game.clone())
+    }
+
+    pub fn game(&self, id: &str) -> Result<Game, GameError> {
+        Ok(self.game_handle(id)?.game.lock().unwrap().clone())
+    }
+
+    /*
+       Führt den Zug des Nutzers aus und lässt die Engine antworten. Die Engine rechnet auf einer Kopie des
+       Spiels, damit der Lock während der Suche frei bleibt.
+       Ist das Spiel danach beendet, wird das Rating des Spielers angepasst
+    */
+    pub fn play(&self, id: &str, column: u8, request_id: Option<&str>) -> Result<Game, GameError> {
+        let entry = self.game_handle(id)?;
+        let mut game = entry.game.lock().unwrap();
+        if entry.thinking.load(Ordering::SeqCst) {
+            return Err(GameError::EngineThinking);
+        }
+        entry.stop_pondering();
+        *entry.last_activity.lock().unwrap() = Instant::now();
+
+        game.play_user_move(column)?;
+        // Zuschauer sehen den Zug des Spielers, bevor die Engine antwortet
+        entry.publish(&game);
+        if !game.is_over() {
+            let mut thinking = game.clone();
+            entry.thinking.store(true, Ordering::SeqCst);
+            drop(game);
+
+            let cancel = CancelToken::new();
+            self.searches.register(id, cancel.clone());
+            let options = self.engine_options(&entry, id, cancel.clone(), request_id);
+            let played = thinking.play_engine_move(&options);
+            self.searches.unregister(id, &cancel);
+
+            game = entry.game.lock().unwrap();
+            entry.thinking.store(false, Ordering::SeqCst);
+            played?;
+            *game = thinking;
+
+            // wurde das Spiel während der Suche beendet, zählt es nicht mehr für das Rating
+            if entry.removed.load(Ordering::SeqCst) {
+                return Err(GameError::NotFound(id.to_string()));
+            }
+        }
+
+        let mut record = None;
+        if let Some(score) = player_score(&game.result) {
+            let mut ratings = self.ratings.lock().unwrap();
+            let rating = ratings.entry(game.player.clone()).or_default();
+            let before = rating.rating;
+            rating.update_assisted(game.level, score, game.takebacks);
+            game.rating_change = Some(rating.rating - before);
+            drop(ratings);
+            record = GameRecord::from_game(&game);
+        }
+
+        entry.publish(&game);
+        self.start_pondering(&entry, &game);
+        // der Verlauf wird erst nach Freigabe des Spiels geschrieben, damit Abfragen nicht auf die Datenbank warten
+        let played = game.clone();
+        let recording = entry.recording.lock().unwrap();
+        drop(game);
+        self.save(record);
+        drop(recording);
+        Ok(played)
+    }
+
+    /*
+       Nimmt den letzten Zug des Spielers samt Antwort der Engine zurück. Nach einer Niederlage läuft das Spiel
+       weiter: die Änderung des Ratings wird zurückgenommen und das Spiel aus dem Verlauf entfernt, bis es erneut
+       endet. Der Verlauf wird in der Datenbank geändert, Aufrufer auf den Workern von actix nutzen web::block
+    */
+    pub fn undo(&self, id: &str) -> Result<Game, GameError> {
+        let entry = self.game_handle(id)?;
+        let mut game = entry.game.lock().unwrap();
+        if entry.thinking.load(Ordering::SeqCst) {
+            return Err(GameError::EngineThinking);
+        }
+        entry.stop_pondering();
+        *entry.last_activity.lock().unwrap() = Instant::now();
+
+        let was_over = game.is_over();
+        let undone = game.undo();
+        if undone.is_ok() {
+            if was_over {
+                let _recording = entry.recording.lock().unwrap();
+                self.reopen(&mut game);
+            }
+            entry.publish(&game);
+        }
+        self.start_pondering(&entry, &game);
+        undone.map(|()| game.clone())
+    }
+
+    /*
+       Aktueller Stand und Empfänger aller folgenden Stände des Spiels, z.B. für Zuschauer. Wird das Spiel
+       entfernt, endet der Empfänger
+    */
+    pub fn watch(&self, id: &str) -> Result<(Game, broadcast::Receiver<Game>), GameError> {
+        let entry = self.game_handle(id)?;
+        let game = entry.game.lock().unwrap();
+        Ok((game.clone(), entry.updates.subscribe()))
+    }
+
+    /*
+       Bricht den laufenden Zug der Engine ab, die Engine spielt dann sofort den besten bisher gefundenen Zug.
+       Gibt false zurück, wenn für das Spiel gerade keine Suche läuft
+    */
+    pub fn cancel_search(&self, id: &str) -> bool {
+        self.searches.cancel(id)
+    }
+
+    // beendet ein Spiel vorzeitig (z.B. "Neues Spiel" in der UI), eine laufende Suche wird abgebrochen
+    pub fn remove_game(&self, id: &str) -> Result<(), GameError> {
+        let removed = self.games.lock().unwrap().remove(id);
+        let entry = removed.ok_or_else(|| GameError::NotFound(id.to_string()))?;
+        entry.removed.store(true, Ordering::SeqCst);
+        self.searches.cancel(id);
+        entry.stop_pondering();
+        Ok(())
+    }
+
+    // entfernt Spiele, in denen länger als max_idle kein Zug gespielt wurde, und gibt deren Anzahl zurück
+    pub fn expire_idle_games(&self, max_idle: Duration) -> usize {
+        let expired: Vec<(String, Arc<GameEntry>)> = {
+            let mut games = self.games.lock().unwrap();
+            let ids: Vec<String> = games
+                .iter()
+                .filter(|(_, entry)| entry.last_activity.lock().unwrap().elapsed() >= max_idle)
+                .map(|(id, _)| id.clone())
+                .collect();
+            ids.iter().filter_map(|id| games.remove_entry(id)).collect()
+        };
+
+        for (id, entry) in expired.iter() {
+            entry.removed.store(true, Ordering::SeqCst);
+            self.searches.cancel(id);
+            entry.stop_pondering();
+        }
+        expired.len()
+    }
+
+    /*
+       Kopie aller Spiele und Ratings. Das Pondering wird dabei beendet, da es beim Herunterfahren
+       nur noch Rechenzeit kosten würde; mit dem nächsten Zug des Spielers beginnt es neu
+    */
+    pub fn snapshot(&self) -> SessionSnapshot {
+        let entries: Vec<Arc<GameEntry>> = self.games.lock().unwrap().values().cloned().collect();
+        let mut games: Vec<Game> = entries
+            .iter()
+            .map(|entry| {
+                entry.stop_pondering();
+                entry.game.lock().unwrap().clone()
+            })
+            .collect();
+        games.sort_by(|a, b| a.id.cmp(&b.id));
+        SessionSnapshot {
+            games,
+            ratings: self.ratings.lock().unwrap().clone(),
+        }
+    }
+
+    /*
+       Übernimmt Spiele und Ratings aus einem Snapshot, die Spiele gelten ab jetzt als aktiv. Gepondert wird erst
+       nach dem nächsten Zug eines Spiels, sonst würde nach dem Start für alle Spiele gleichzeitig gerechnet
+    */
+    pub fn restore(&self, snapshot: SessionSnapshot) -> usize {
+        let restored = snapshot.games.len();
+        self.ratings.lock().unwrap().extend(snapshot.ratings);
+        for mut game in snapshot.games {
+            if game.takeback_limit == UNKNOWN_TAKEBACK_LIMIT {
+                game.takeback_limit = self.takeback_limits.limit(game.level);
+            }
+            let entry = Arc::new(GameEntry::new(game.clone()));
+            self.games.lock().unwrap().insert(game.id.clone(), entry);
+        }
+        restored
+    }
+
+    // schreibt den Snapshot zuerst in eine temporäre Datei, damit ein Absturz keine halbe Datei hinterlässt
+    pub fn save_snapshot(&self, path: &Path) -> Result<usize, String> {
+        if self.kept_snapshot.lock().unwrap().as_deref() == Some(path) {
+            return Err(format!(
+                "session snapshot {} could not be loaded and is not overwritten",
+                path.display()
+            ));
+        }
+        let snapshot = self.snapshot();
+        let json = serde_json::to_string(&snapshot)
+            .map_err(|error| format!("failed to serialize sessions: {error}"))?;
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, json)
+            .and_then(|()| fs::rename(&temporary, path))
+            .map_err(|error| format!("failed to write sessions {}: {error}", path.display()))?;
+        Ok(snapshot.games.len())
+    }
+
+    /*
+       Lädt einen gespeicherten Snapshot, ohne Datei gibt es nichts wiederherzustellen. Eine ungültige Datei wird
+       als <Datei>.corrupt-<Unix-Zeit> beiseitegelegt, damit das Speichern beim Herunterfahren sie nicht
+       überschreibt. Gelingt das nicht, wird sie von save_snapshot nicht angetastet
+    */
+    pub fn load_snapshot(&self, path: &Path) -> Result<usize, String> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(error) => {
+                *self.kept_snapshot.lock().unwrap() = Some(path.to_path_buf());
+                return Err(format!(
+                    "failed to read sessions {}: {error}",
+                    path.display()
+                ));
+            }
+        };
+        let error = match serde_json::from_str(&json) {
+            Ok(snapshot) => return Ok(self.restore(snapshot)),
+            Err(error) => format!("invalid session snapshot {}: {error}", path.display()),
+        };
+
+        let mut corrupt = path.as_os_str().to_owned();
+        corrupt.push(format!(".corrupt-{}", unix_time()));
+        let corrupt = PathBuf::from(corrupt);
+        match fs::rename(path, &corrupt) {
+            Ok(()) => Err(format!("{error}, moved to {}", corrupt.display())),
+            Err(rename_error) => {
+                *self.kept_snapshot.lock().unwrap() = Some(path.to_path_buf());
+                Err(format!(
+                    "{error}, failed to move it aside ({rename_error}), it will not be overwritten"
+                ))
+            }
+        }
+    }
+
+    // Anzahl der Spiele im Speicher, auch beendete bis zu ihrem Ablauf
+    pub fn len(&self) -> usize {
+        self.games.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Anzahl der Spiele, für die gerade im Hintergrund gerechnet wird
+    pub fn pondering_games(&self) -> usize {
+        self.pondering.load(Ordering::SeqCst)
+    }
+
+    // bisher angelegte Tabellen fürs Pondering, höchstens eine je Kern
+    pub fn ponder_tables(&self) -> usize {
+        self.ponder_tables.allocated.load(Ordering::SeqCst)
+    }
+
+    pub fn rating(&self, player: &str) -> PlayerRating {
+        self.ratings
+            .lock()
+            .unwrap()
+            .get(player)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // Sucheinstellungen für einen Zug der Engine, beim Pondering mit der Transpositionstabelle des Spiels
+    fn engine_options(
+        &self,
+        entry: &GameEntry,
+        id: &str,
+        cancel: CancelToken,
+        request_id: Option<&str>,
+    ) -> SearchOptions {
+        SearchOptions {
+            transposition_table: entry
+                .table()
+                .or_else(|| self.search_options.transposition_table.clone()),
+            cancel,
+            request_id: request_id.map(String::from),
+            game_id: Some(id.to_string()),
+            ..self.search_options.clone()
+        }
+    }
+
+    /*
+       Startet nach dem Zug der Engine die Hintergrundsuche auf die möglichen Antworten des Spielers.
+       Damit das Pondering den eigentlichen Suchen keine Rechenzeit nimmt, wird höchstens für so viele
+       Spiele gleichzeitig gerechnet, wie Kerne vorhanden sind. Ebenso viele Tabellen gibt es, ein Spiel
+       ohne Tabelle pondert nicht
+    */
+    fn start_pondering(&self, entry: &GameEntry, game: &Game) {
+        let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+        if game.is_over() {
+            entry.transposition_table.lock().unwrap().take();
+        }
+        if !game.ponder || game.is_over() {
+            return;
+        }
+        let table = {
+            let mut table = entry.transposition_table.lock().unwrap();
+            if table.is_none() {
+                *table = self
+                    .ponder_tables
+                    .acquire(cores, self.search_options.transposition_table_entries);
+            }
+            match table.as_ref() {
+                Some(table) => table.table.clone(),
+                None => return,
+            }
+        };
+        let Ok(mut difficulty) = Difficulty::from_level(game.level) else {
+            return;
+        };
+        // mit fester Tiefe ändert das Pondering die Züge der Engine nicht, das Spiel bleibt reproduzierbar
+        if game.reproducible {
+            difficulty = difficulty.fixed_depth();
+        }
+
+        if self.pondering.fetch_add(1, Ordering::SeqCst) >= cores {
+            self.pondering.fetch_sub(1, Ordering::SeqCst);
+            return;
+        }
+
+        let cancel = CancelToken::new();
+        let options = SearchOptions {
+            threads: 1,
+            transposition_table: Some(table),
+            cancel: cancel.clone(),
+            progress: None,
+            ..self.search_options.clone()
+        };
+        let game_board = game.board.clone();
+        let computer_started = game.computer_started;
+        let finished = Arc::new(AtomicBool::new(false));
+        let (thread_finished, pondering) = (finished.clone(), self.pondering.clone());
+        thread::spawn(move || {
+            ponder(&game_board, computer_started, &difficulty, &options);
+            Pondering::finish(&thread_finished, &pondering);
+        });
+
+        *entry.pondering.lock().unwrap() = Some(Pondering {
+            cancel,
+            finished,
+            pondering: self.pondering.clone(),
+        });
+    }
+
+    // nimmt das Ergebnis eines wieder geöffneten Spiels aus Rating und Verlauf zurück
+    fn reopen(&self, game: &mut Game) {
+        if let Some(change) = game.rating_change.take() {
+            if let Some(rating) = self.ratings.lock().unwrap().get_mut(&game.player) {
+                rating.rating -= change;
+                rating.games = rating.games.saturating_sub(1);
+            }
+        }
+        let Some(history) = &self.history else {
+            return;
+        };
+        if let Err(error) = history.remove(&game.id) {
+            log::error!(
+                "Spiel {} wurde nicht aus dem Verlauf entfernt: {error}",
+                game.id
+            );
+        }
+    }
+
+    // ein Fehler beim Speichern beendet das Spiel nicht, es fehlt dann nur im Verlauf
+    fn save(&self, record: Option<GameRecord>) {
+        let (Some(history), Some(record)) = (&self.history, record) else {
+            return;
+        };
+        if let Err(error) = history.record(&record) {
+            log::error!("Spiel {} wurde nicht gespeichert: {error}", record.id);
+        }
+    }
+
+    fn game_handle(&self, id: &str) -> Result<Arc<GameEntry>, GameError> {
+        self.games
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| GameError::NotFound(id.to_string()))
+    }
+}
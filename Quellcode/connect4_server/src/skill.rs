@@ -0,0 +1,119 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use rand::Rng;
+
+use crate::connect4ai::{Field, THREAT_L4_SCORE, ZUGZWANG_SCORE};
+
+// Scores werden für die Zugauswahl in "Bedrohungs-Einheiten" umgerechnet und auf diesen Wert begrenzt,
+// damit Sieg- und Zugzwang-Scores die Softmax-Verteilung nicht vollständig dominieren
+const SKILL_SCORE_CAP: f64 = 20.0;
+
+// ein Zug gilt als Patzer, wenn er mindestens so viel Score wie ein verlorener Zugzwang kostet
+pub const BLUNDER_SCORE_LOSS: i64 = ZUGZWANG_SCORE;
+
+/*
+   Menschenähnliches Schwächemodell einer Schwierigkeitsstufe
+    - temperature: Temperatur der Softmax-Verteilung über die Züge (0 -> immer der beste Zug)
+    - threat_oversight: Wahrscheinlichkeit, mit der gegnerische Bedrohungen übersehen werden
+    - blunder_budget: maximale Anzahl an Patzern pro Spiel
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Skill {
+    pub temperature: f64,
+    pub threat_oversight: f64,
+    pub blunder_budget: u8,
+}
+
+impl Skill {
+    pub const PERFECT: Skill = Skill {
+        temperature: 0.0,
+        threat_oversight: 0.0,
+        blunder_budget: 0,
+    };
+
+    pub const fn new(temperature: f64, threat_oversight: f64, blunder_budget: u8) -> Skill {
+        Skill {
+            temperature,
+            threat_oversight,
+            blunder_budget,
+        }
+    }
+
+    // ein perfekter Spieler wählt immer den Zug der Suche und benötigt keine Bewertung aller Züge
+    pub fn is_perfect(&self) -> bool {
+        self.temperature <= 0.0 && self.threat_oversight <= 0.0
+    }
+
+    // würfelt aus, ob in diesem Zug gegnerische Bedrohungen übersehen werden
+    pub fn overlooks_threats<R: Rng>(&self, rng: &mut R) -> bool {
+        self.threat_oversight > 0.0 && rng.gen_bool(self.threat_oversight.min(1.0))
+    }
+
+    /*
+       Wählt aus den bewerteten Zügen einen Zug mithilfe einer Softmax-Verteilung aus
+        - perceived_scores: die Scores, so wie der Computer sie wahrnimmt (ggf. mit übersehenen Bedrohungen)
+        - true_scores: die tatsächlichen Scores der Suche, anhand derer Patzer erkannt werden
+        - blunders_made: Anzahl der bereits in diesem Spiel gemachten Patzer
+       Gibt den gewählten Zug und ob es sich um einen Patzer handelt zurück
+    */
+    pub fn choose_move<R: Rng>(
+        &self,
+        perceived_scores: &[(Field, i64)],
+        true_scores: &[(Field, i64)],
+        blunders_made: u8,
+        rng: &mut R,
+    ) -> Option<(Field, bool)> {
+        let best_score = true_scores.iter().map(|(_, score)| *score).max()?;
+        let budget_left = blunders_made < self.blunder_budget;
+
+        // ist das Patzer-Budget aufgebraucht, kommen nur noch Züge ohne großen Score-Verlust in Frage
+        let candidates: Vec<(Field, i64, bool)> = perceived_scores
+            .iter()
+            .zip(true_scores.iter())
+            .map(|((field, perceived), (_, actual))| {
+                (*field, *perceived, is_blunder(best_score, *actual))
+            })
+            .filter(|(_, _, blunder)| budget_left || !blunder)
+            .collect();
+
+        let max_utility = candidates
+            .iter()
+            .map(|(_, score, _)| utility(*score))
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        // Temperatur 0: wähle deterministisch den (wahrgenommen) besten Zug
+        if self.temperature <= 0.0 {
+            return candidates
+                .iter()
+                .find(|(_, score, _)| utility(*score) == max_utility)
+                .map(|(field, _, blunder)| (*field, *blunder));
+        }
+
+        let weights: Vec<f64> = candidates
+            .iter()
+            .map(|(_, score, _)| ((utility(*score) - max_utility) / self.temperature).exp())
+            .collect();
+        let total: f64 = weights.iter().sum();
+
+        let mut target = rng.gen_range(0.0..total);
+        for ((field, _, blunder), weight) in candidates.iter().zip(weights.iter()) {
+            if target < *weight {
+                return Some((*field, *blunder));
+            }
+            target -= weight;
+        }
+
+        candidates
+            .last()
+            .map(|(field, _, blunder)| (*field, *blunder))
+    }
+}
+
+pub fn is_blunder(best_score: i64, score: i64) -> bool {
+    (best_score as i128 - score as i128) >= BLUNDER_SCORE_LOSS as i128
+}
+
+// rechnet einen Score in begrenzte Bedrohungs-Einheiten für die Softmax-Verteilung um
+fn utility(score: i64) -> f64 {
+    (score as f64 / THREAT_L4_SCORE as f64).clamp(-SKILL_SCORE_CAP, SKILL_SCORE_CAP)
+}
@@ -0,0 +1,276 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::OnceCell;
+
+use crate::cancellation::CancelToken;
+use crate::connect4ai::{
+    analyse_moves, moves_to_loss, moves_to_win, Difficulty, GameBoard, NextMoveResult,
+    SearchOptions,
+};
+use crate::ladder::HARD_LEVEL;
+use crate::multiplayer::Match;
+use crate::notation::{Outcome, Side, ENGINE_NAME};
+use crate::pool::ComputePool;
+use crate::session::Game;
+
+// Stufe, mit der die Engine die Stellungen für Zuschauer bewertet
+pub const EVALUATION_LEVEL: u8 = HARD_LEVEL;
+/*
+   Eigener kleiner Pool für die Bewertungen, damit Zuschauer den Spielern keine Rechenzeit wegnehmen.
+   Ist er voll, bleibt die Stellung unbewertet, bis ein weiterer Zuschauer oder ein neuer Zug sie anfordert
+*/
+pub const EVALUATION_WORKERS: usize = 1;
+pub const EVALUATION_QUEUE_LIMIT: usize = 4;
+pub const EVALUATION_TIMEOUT: Duration = Duration::from_secs(3);
+
+/*
+   Stand eines Spiels für Zuschauer, gleich für Spiele gegen die Engine und zwischen zwei Menschen
+    - board: Rot mit den Steinen des Nutzers, Gelb mit denen des Computers, wie beim Nachspielen einer Aufzeichnung
+    - red / yellow: Namen der Spieler, die Engine heißt wie in den Aufzeichnungen, None solange ein Platz frei ist
+*/
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Position {
+    pub id: String,
+    pub red: Option<String>,
+    pub yellow: Option<String>,
+    pub board: GameBoard,
+    pub moves: Vec<u8>,
+    pub to_move: Side,
+    pub outcome: Outcome,
+}
+
+impl Position {
+    pub fn from_game(game: &Game) -> Position {
+        let (player_side, board) = if game.computer_started {
+            (Side::Yellow, game.board.swap_players())
+        } else {
+            (Side::Red, game.board.clone())
+        };
+        let (red, yellow) = match player_side {
+            Side::Red => (game.player.clone(), ENGINE_NAME.to_string()),
+            Side::Yellow => (ENGINE_NAME.to_string(), game.player.clone()),
+        };
+        Position {
+            id: game.id.clone(),
+            red: Some(red),
+            yellow: Some(yellow),
+            board,
+            moves: game.moves.clone(),
+            to_move: Side::to_move(game.moves.len()),
+            outcome: match game.result {
+                NextMoveResult::PlayerWins => Outcome::win(player_side),
+                NextMoveResult::ComputerWins => Outcome::win(player_side.other()),
+                NextMoveResult::Draw => Outcome::Draw,
+                NextMoveResult::NextMove | NextMoveResult::None => Outcome::Ongoing,
+            },
+        }
+    }
+
+    pub fn from_match(game: &Match) -> Position {
+        Position {
+            id: game.id.clone(),
+            red: game.red.clone(),
+            yellow: game.yellow.clone(),
+            board: game.board.clone(),
+            moves: game.moves.clone(),
+            to_move: game.to_move,
+            outcome: game.outcome,
+        }
+    }
+}
+
+/*
+   Bewertung der Stellung durch die Engine, alle Scores aus Sicht der ziehenden Seite
+    - ply: Anzahl der Züge, nach denen die Stellung bewertet wurde
+    - best_column / pv: bester Zug und Hauptvariante beginnend mit ihm, fehlen solange das Spiel läuft, damit
+      Zuschauer (und die Spieler selbst) die Engine nicht als Hilfe nutzen können
+    - win_in / loss_in: Anzahl der Züge bis zum erzwungenen Sieg bzw. zur erzwungenen Niederlage der ziehenden Seite
+*/
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Evaluation {
+    pub ply: usize,
+    pub to_move: Side,
+    pub depth: u8,
+    pub score: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_column: Option<u8>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pv: Vec<u8>,
+    pub win_in: Option<u8>,
+    pub loss_in: Option<u8>,
+}
+
+/*
+   Bewertet die Stellung für die ziehende Seite. Die Engine sucht immer für den Computer, deshalb werden die
+   Steine getauscht, wenn Rot am Zug ist. None bei beendeten Spielen und abgebrochenen Suchen
+*/
+pub fn evaluate(
+    position: &Position,
+    difficulty: &Difficulty,
+    options: &SearchOptions,
+) -> Option<Evaluation> {
+    if position.outcome != Outcome::Ongoing {
+        return None;
+    }
+    let board = match position.to_move {
+        Side::Red => position.board.swap_players(),
+        Side::Yellow => position.board.clone(),
+    };
+    let analysis = analyse_moves(&board, position.to_move == Side::Red, difficulty, options)?;
+    Some(Evaluation {
+        ply: position.moves.len(),
+        to_move: position.to_move,
+        depth: analysis.depth,
+        score: analysis.best_score,
+        best_column: Some(analysis.best_column),
+        pv: analysis.pv,
+        win_in: moves_to_win(analysis.best_score),
+        loss_in: moves_to_loss(analysis.best_score),
+    })
+}
+
+impl Evaluation {
+    // Bewertung ohne besten Zug und Hauptvariante, für Stellungen eines laufenden Spiels
+    pub fn without_hint(&self) -> Evaluation {
+        Evaluation {
+            best_column: None,
+            pv: Vec::new(),
+            ..self.clone()
+        }
+    }
+}
+
+// Nachrichten an Zuschauer, z.B. {"type": "position", "id": ..., "moves": [3, 2], ...}
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SpectatorEvent {
+    Position(Position),
+    Evaluation(Evaluation),
+}
+
+/*
+   Bewertung einer Stellung, geteilt von allen Zuschauern des Spiels
+    - result: enthält nur fertige Bewertungen, eine abgelehnte oder abgebrochene Suche wird später wiederholt
+    - cancel: bricht die Suche ab, sobald eine neue Stellung bewertet wird oder der letzte Zuschauer geht
+*/
+#[derive(Clone, Default)]
+pub struct SharedEvaluation {
+    pub result: Arc<OnceCell<Evaluation>>,
+    pub cancel: CancelToken,
+}
+
+/*
+   Zuschauer eines Spiels
+    - moves: Stellung der zuletzt angeforderten Bewertung
+    - evaluation: deren Bewertung, die Engine rechnet für alle Zuschauer eines Spiels nur einmal
+*/
+struct Audience {
+    spectators: usize,
+    moves: Vec<u8>,
+    evaluation: SharedEvaluation,
+}
+
+/*
+   Zählt die Zuschauer je Spiel (Spiele gegen die Engine und zwischen zwei Menschen haben verschiedene Ids)
+   und teilt die Bewertung der aktuellen Stellung zwischen ihnen
+*/
+pub struct Spectators {
+    audiences: Mutex<HashMap<String, Audience>>,
+    pool: ComputePool,
+}
+
+impl Default for Spectators {
+    fn default() -> Spectators {
+        Spectators {
+            audiences: Mutex::default(),
+            pool: ComputePool::new(
+                EVALUATION_WORKERS,
+                EVALUATION_QUEUE_LIMIT,
+                EVALUATION_TIMEOUT,
+            ),
+        }
+    }
+}
+
+impl Spectators {
+    pub fn new() -> Spectators {
+        Spectators::default()
+    }
+
+    pub fn pool(&self) -> &ComputePool {
+        &self.pool
+    }
+
+    // meldet einen Zuschauer an, bis der zurückgegebene Guard verworfen wird
+    pub fn watch(self: &Arc<Self>, id: &str) -> SpectatorGuard {
+        self.audiences
+            .lock()
+            .unwrap()
+            .entry(id.to_string())
+            .or_insert_with(|| Audience {
+                spectators: 0,
+                moves: Vec::new(),
+                evaluation: SharedEvaluation::default(),
+            })
+            .spectators += 1;
+        SpectatorGuard {
+            spectators: self.clone(),
+            id: id.to_string(),
+        }
+    }
+
+    pub fn count(&self, id: &str) -> usize {
+        self.audiences
+            .lock()
+            .unwrap()
+            .get(id)
+            .map_or(0, |audience| audience.spectators)
+    }
+
+    /*
+       Bewertung der Stellung nach den übergebenen Zügen. Wer sie zuerst anfordert, lässt die Engine rechnen,
+       alle anderen warten auf dasselbe Ergebnis. Eine neue Stellung ersetzt die Bewertung der vorherigen und
+       bricht deren Suche ab. Eine fehlgeschlagene Suche hat ihr Token bereits abgebrochen und wird ebenfalls ersetzt
+    */
+    pub fn evaluation(&self, id: &str, moves: &[u8]) -> SharedEvaluation {
+        let mut audiences = self.audiences.lock().unwrap();
+        let Some(audience) = audiences.get_mut(id) else {
+            return SharedEvaluation::default();
+        };
+        let failed =
+            audience.evaluation.cancel.is_cancelled() && !audience.evaluation.result.initialized();
+        if audience.moves != moves || failed {
+            audience.evaluation.cancel.cancel();
+            audience.moves = moves.to_vec();
+            audience.evaluation = SharedEvaluation::default();
+        }
+        audience.evaluation.clone()
+    }
+
+    fn leave(&self, id: &str) {
+        let mut audiences = self.audiences.lock().unwrap();
+        if let Some(audience) = audiences.get_mut(id) {
+            audience.spectators -= 1;
+            if audience.spectators == 0 {
+                audience.evaluation.cancel.cancel();
+                audiences.remove(id);
+            }
+        }
+    }
+}
+
+pub struct SpectatorGuard {
+    spectators: Arc<Spectators>,
+    id: String,
+}
+
+impl Drop for SpectatorGuard {
+    fn drop(&mut self) {
+        self.spectators.leave(&self.id);
+    }
+}
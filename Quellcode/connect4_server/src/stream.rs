@@ -0,0 +1,106 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use actix_web::web::{Bytes, Json};
+use actix_web::{post, web, HttpResponse, Responder};
+use futures_util::stream;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::cancellation::SearchRegistry;
+use crate::config::Config;
+use crate::connect4ai::{next_move_with, rng_from_seed, GameBoard, SearchInfo, SearchOptions};
+use crate::logging::RequestId;
+use crate::pool::ComputePool;
+use crate::{cancel_guard, next_move_response, pool_error_response, ErrorResponse, NextMoveInfo};
+
+// ein Server-Sent Event mit JSON-Daten
+fn event(name: &str, data: &impl Serialize) -> Bytes {
+    let data = serde_json::to_string(data).unwrap();
+    Bytes::from(format!("event: {name}\ndata: {data}\n\n"))
+}
+
+// mit der Id kann die Suche über DELETE /searches/{id} abgebrochen werden
+#[derive(Debug, Serialize)]
+struct SearchStarted {
+    search_id: String,
+}
+
+/*
+   Wie /next_move, sendet aber den Fortschritt der Suche als Server-Sent Events:
+    - "search" zu Beginn mit der Id der Suche
+    - "info" nach jeder Iteration mit Tiefe, bester Spalte, Score, Knoten und Hauptvariante
+    - "move" am Ende mit derselben Antwort wie /next_move
+   Schließt der Client den Stream, wird die Suche abgebrochen
+*/
+#[post("next_move/stream")]
+async fn next_move_stream(
+    game_board: Json<GameBoard>,
+    info: web::Query<NextMoveInfo>,
+    search_options: web::Data<SearchOptions>,
+    pool: web::Data<ComputePool>,
+    registry: web::Data<SearchRegistry>,
+    config: web::Data<Config>,
+    request_id: RequestId,
+) -> impl Responder {
+    let difficulty = match info.difficulty(config.default_level) {
+        Ok(difficulty) => difficulty,
+        Err(error) => return HttpResponse::BadRequest().json(ErrorResponse::new(error)),
+    };
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let search_id = format!("{:016x}", rand::random::<u64>());
+    let (cancel, guard) = cancel_guard(registry, Some(&search_id));
+    let _ = sender.send(event("search", &SearchStarted { search_id }));
+    let progress_sender = sender.clone();
+    let search_options = SearchOptions {
+        threads: info.threads(search_options.threads),
+        cancel: cancel.clone(),
+        progress: Some(Arc::new(move |search_info: &SearchInfo| {
+            let _ = progress_sender.send(event("info", search_info));
+        })),
+        request_id: Some(request_id.0),
+        ..search_options.as_ref().clone()
+    };
+
+    let mut game_board = game_board.into_inner();
+    let computer_started = info.computer_started;
+    let blunders = info.blunders;
+    let seed = info.seed();
+    let submitted = pool.submit(move || {
+        let result = next_move_with(
+            &mut game_board,
+            computer_started,
+            &difficulty,
+            blunders,
+            &mut rng_from_seed(Some(seed)),
+            &search_options,
+        );
+        let _ = sender.send(event("move", &next_move_response(game_board, result, seed)));
+    });
+    if let Err(error) = submitted {
+        return pool_error_response(error);
+    }
+
+    // nach dem Timeout wird die Suche abgebrochen und der beste bisher gefundene Zug gesendet
+    let timeout = pool.timeout();
+    actix_web::rt::spawn(async move {
+        actix_web::rt::time::sleep(timeout).await;
+        cancel.cancel();
+    });
+
+    // der Guard lebt so lange wie der Stream und bricht die Suche ab, wenn der Client ihn vorzeitig schließt
+    let events = stream::unfold((receiver, guard), |(mut receiver, guard)| async move {
+        let event = receiver.recv().await?;
+        Some((Ok::<_, Infallible>(event), (receiver, guard)))
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events)
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(next_move_stream);
+}
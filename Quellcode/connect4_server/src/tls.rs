@@ -0,0 +1,140 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::ServerConfig;
+
+#[derive(Debug, PartialEq)]
+pub enum TlsError {
+    Certificate(PathBuf, String),
+    Key(PathBuf, String),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsError::Certificate(path, error) => {
+                write!(f, "invalid tls certificate {}: {error}", path.display())
+            }
+            TlsError::Key(path, error) => write!(f, "invalid tls key {}: {error}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
+
+/*
+   Zertifikat und Schlüssel des Servers im PEM-Format. Der Store wird rustls als Quelle des Zertifikats
+   übergeben, damit ein neu geladenes Zertifikat ohne Neustart für alle folgenden Verbindungen gilt
+*/
+#[derive(Debug)]
+pub struct CertificateStore {
+    certificate_path: PathBuf,
+    key_path: PathBuf,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl CertificateStore {
+    pub fn load(certificate_path: &Path, key_path: &Path) -> Result<CertificateStore, TlsError> {
+        let provider = Arc::new(ring::default_provider());
+        let current = load_certified_key(certificate_path, key_path, &provider)?;
+        Ok(CertificateStore {
+            certificate_path: certificate_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            provider,
+            current: RwLock::new(Arc::new(current)),
+        })
+    }
+
+    // liest Zertifikat und Schlüssel neu ein, bei einem Fehler bleibt das bisherige Zertifikat aktiv
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let certified_key =
+            load_certified_key(&self.certificate_path, &self.key_path, &self.provider)?;
+        *self.current.write().unwrap() = Arc::new(certified_key);
+        Ok(())
+    }
+
+    pub fn server_config(self: &Arc<Self>) -> ServerConfig {
+        ServerConfig::builder_with_provider(self.provider.clone())
+            .with_safe_default_protocol_versions()
+            .expect("ring supports the default protocol versions")
+            .with_no_client_auth()
+            .with_cert_resolver(self.clone())
+    }
+}
+
+impl ResolvesServerCert for CertificateStore {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+fn load_certified_key(
+    certificate_path: &Path,
+    key_path: &Path,
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey, TlsError> {
+    let certificate_error =
+        |error: String| TlsError::Certificate(certificate_path.to_path_buf(), error);
+    let certificates = CertificateDer::pem_file_iter(certificate_path)
+        .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+        .map_err(|error| certificate_error(error.to_string()))?;
+    if certificates.is_empty() {
+        return Err(certificate_error("no certificate found".to_string()));
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|error| TlsError::Key(key_path.to_path_buf(), error.to_string()))?;
+    CertifiedKey::from_der(certificates, key, provider)
+        .map_err(|error| TlsError::Key(key_path.to_path_buf(), error.to_string()))
+}
+
+/*
+   Lädt das Zertifikat bei SIGHUP neu, z.B. nachdem es durch ein erneuertes ersetzt wurde.
+   Muss innerhalb der Laufzeit von actix aufgerufen werden
+*/
+#[cfg(unix)]
+pub fn reload_on_sighup(store: Arc<CertificateStore>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    actix_web::rt::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(error) => {
+                log::error!("SIGHUP kann nicht empfangen werden: {error}");
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            match store.reload() {
+                Ok(()) => log::info!("TLS-Zertifikat neu geladen"),
+                Err(error) => log::error!("{error}, das bisherige Zertifikat bleibt aktiv"),
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn reload_on_sighup(_store: Arc<CertificateStore>) {}
+
+// Ziel einer Weiterleitung von HTTP auf HTTPS, der Port im Host der Anfrage wird ersetzt
+pub fn https_location(host: &str, https_port: u16, path_and_query: &str) -> String {
+    let hostname = match host.rsplit_once(':') {
+        // IPv6-Adressen ohne Port enthalten ebenfalls Doppelpunkte, sind aber in Klammern gefasst
+        Some((hostname, port)) if !port.contains(']') => hostname,
+        _ => host,
+    };
+    if https_port == 443 {
+        format!("https://{hostname}{path_and_query}")
+    } else {
+        format!("https://{hostname}:{https_port}{path_and_query}")
+    }
+}
@@ -0,0 +1,192 @@
+#![allow(dead_code)] // suppress weird clippy behaviour where used code is marked as unused
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::connect4ai::{GameBoard, HEIGHT, WIDTH};
+
+// Standardgröße einer Tabelle: 2^16 Einträge zu je 16 Byte (1 MiB)
+pub const DEFAULT_ENTRIES: usize = 1 << 16;
+
+const SCORE_BITS: u32 = 41;
+const SCORE_OFFSET: i64 = 1 << (SCORE_BITS - 1);
+const OCCUPIED: u64 = 1 << 63;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    // der tatsächliche Score ist mindestens so groß (Beta-Cutoff)
+    Lower,
+    // der tatsächliche Score ist höchstens so groß (kein Zug war besser als Alpha)
+    Upper,
+}
+
+/*
+   Ein Eintrag der Transpositionstabelle
+    - depth: Resttiefe, mit der die Stellung durchsucht wurde
+    - score: Score relativ zur gespeicherten Stellung (Sieg-Scores ohne Distanz zur Wurzel)
+    - best_move: Spalte des besten gefundenen Zugs
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TtEntry {
+    pub depth: u8,
+    pub score: i64,
+    pub bound: Bound,
+    pub best_move: Option<u8>,
+}
+
+impl TtEntry {
+    fn pack(&self) -> u64 {
+        let score = (self.score.clamp(-SCORE_OFFSET, SCORE_OFFSET - 1) + SCORE_OFFSET) as u64;
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let best_move = self.best_move.map_or(0, |column| column as u64 + 1);
+
+        OCCUPIED
+            | score
+            | (self.depth as u64) << SCORE_BITS
+            | bound << (SCORE_BITS + 8)
+            | best_move << (SCORE_BITS + 10)
+    }
+
+    fn unpack(data: u64) -> TtEntry {
+        let score = (data & ((1 << SCORE_BITS) - 1)) as i64 - SCORE_OFFSET;
+        let depth = (data >> SCORE_BITS) as u8;
+        let bound = match (data >> (SCORE_BITS + 8)) & 0b11 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        let best_move = match (data >> (SCORE_BITS + 10)) & 0b1111 {
+            0 => None,
+            column => Some(column as u8 - 1),
+        };
+
+        TtEntry {
+            depth,
+            score,
+            bound,
+            best_move,
+        }
+    }
+}
+
+/*
+   Lock-freie Transpositionstabelle, die sich mehrere Suchthreads teilen.
+   Jeder Eintrag besteht aus zwei Wörtern: (Schlüssel XOR Daten, Daten). Wird ein Eintrag gleichzeitig
+   von zwei Threads geschrieben, passt der Schlüssel beim Lesen nicht mehr und der Eintrag wird verworfen.
+*/
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>,
+    probes: AtomicU64,
+    hits: AtomicU64,
+}
+
+// Anzahl der Einträge, die in eine Tabelle mit der übergebenen Größe in MiB passen
+pub fn entries_for_size_mb(megabytes: usize) -> usize {
+    megabytes * 1024 * 1024 / std::mem::size_of::<[AtomicU64; 2]>()
+}
+
+impl TranspositionTable {
+    // die Anzahl der Einträge wird auf die nächste Zweierpotenz aufgerundet
+    pub fn new(entries: usize) -> TranspositionTable {
+        let entries = entries.max(1).next_power_of_two();
+        TranspositionTable {
+            slots: (0..entries)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+            probes: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_size_mb(megabytes: usize) -> TranspositionTable {
+        TranspositionTable::new(entries_for_size_mb(megabytes))
+    }
+
+    pub fn entries(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        self.probes.fetch_add(1, Ordering::Relaxed);
+        let slot = &self.slots[self.index(key)];
+        let checked_key = slot[0].load(Ordering::Relaxed);
+        let data = slot[1].load(Ordering::Relaxed);
+
+        if data & OCCUPIED == 0 || checked_key ^ data != key {
+            return None;
+        }
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Some(TtEntry::unpack(data))
+    }
+
+    // Einträge anderer Stellungen werden immer, Einträge derselben Stellung nur bei mindestens gleicher Tiefe ersetzt
+    pub fn store(&self, key: u64, entry: TtEntry) {
+        let slot = &self.slots[self.index(key)];
+        let checked_key = slot[0].load(Ordering::Relaxed);
+        let data = slot[1].load(Ordering::Relaxed);
+        if data & OCCUPIED != 0
+            && checked_key ^ data == key
+            && TtEntry::unpack(data).depth > entry.depth
+        {
+            return;
+        }
+
+        let data = entry.pack();
+        slot[0].store(key ^ data, Ordering::Relaxed);
+        slot[1].store(data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot[0].store(0, Ordering::Relaxed);
+            slot[1].store(0, Ordering::Relaxed);
+        }
+    }
+
+    // (Anfragen, Treffer) seit dem Anlegen der Tabelle
+    pub fn statistics(&self) -> (u64, u64) {
+        (
+            self.probes.load(Ordering::Relaxed),
+            self.hits.load(Ordering::Relaxed),
+        )
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key as usize) & (self.slots.len() - 1)
+    }
+}
+
+/*
+   Schlüssel einer Stellung für die Transpositionstabelle. Da die Bewertung vom beginnenden Spieler
+   und der Zugzwang-Bewertung abhängt, fließen beide mit in den Schlüssel ein
+*/
+pub fn position_key(
+    game_board: &GameBoard,
+    player_started: bool,
+    zugzwang_evaluation: bool,
+) -> u64 {
+    let mut stones = [0u64; 2];
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let field = game_board.get(x, y);
+            if field != 0 {
+                stones[field as usize - 1] |= 1 << (y * WIDTH + x);
+            }
+        }
+    }
+
+    let settings = (player_started as u64) << 1 | zugzwang_evaluation as u64;
+    mix(stones[0] ^ mix(stones[1] ^ mix(settings)))
+}
+
+// splitmix64-Finalizer für eine gute Verteilung der Schlüssel
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,307 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc;
+
+use crate::cancellation::{CancelGuard, SearchRegistry};
+use crate::connect4ai::{Difficulty, SearchOptions};
+use crate::matches::{send, CLIENT_TIMEOUT, HEARTBEAT_INTERVAL};
+use crate::multiplayer::{MatchEvent, MatchStore};
+use crate::notation::Outcome;
+use crate::session::{Game, SessionStore};
+use crate::spectators::{
+    evaluate, Evaluation, Position, SpectatorEvent, Spectators, EVALUATION_LEVEL,
+};
+use crate::ErrorResponse;
+
+// Stände eines Spiels gegen die Engine bzw. zwischen zwei Menschen
+enum Feed {
+    Game(Arc<SessionStore>, String, Receiver<Game>),
+    Match(Arc<MatchStore>, String, Receiver<MatchEvent>),
+}
+
+impl Feed {
+    // nächster Stand des Spiels, None wenn das Spiel entfernt wurde
+    async fn next(&mut self) -> Option<Position> {
+        loop {
+            match self {
+                Feed::Game(store, id, updates) => {
+                    return match updates.recv().await {
+                        Ok(game) => Some(Position::from_game(&game)),
+                        // verpasste Stände werden übersprungen, der aktuelle Stand genügt
+                        Err(RecvError::Lagged(_)) => {
+                            store.game(id).ok().map(|game| Position::from_game(&game))
+                        }
+                        Err(RecvError::Closed) => None,
+                    };
+                }
+                Feed::Match(store, id, events) => match events.recv().await {
+                    // Anwesenheit der Spieler und abgelehnte Befehle ändern die Stellung nicht
+                    Ok(MatchEvent::Presence { .. } | MatchEvent::Error { .. }) => {}
+                    Ok(_) | Err(RecvError::Lagged(_)) => {
+                        return store.get(id).ok().map(|game| Position::from_match(&game))
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+            }
+        }
+    }
+}
+
+/*
+   Lässt die Engine die Stellungen eines Zuschauers bewerten. Die Bewertung läuft im Pool der Zuschauer und wird
+   mit allen Zuschauern desselben Spiels geteilt, das Ergebnis kommt zusammen mit den Zügen der Stellung
+   über results zurück
+*/
+struct Evaluator {
+    spectators: Arc<Spectators>,
+    search_options: Arc<SearchOptions>,
+    registry: Arc<SearchRegistry>,
+    results: mpsc::UnboundedSender<(Vec<u8>, Evaluation)>,
+}
+
+impl Evaluator {
+    fn new(
+        spectators: web::Data<Spectators>,
+        search_options: web::Data<SearchOptions>,
+        registry: web::Data<SearchRegistry>,
+    ) -> (Evaluator, mpsc::UnboundedReceiver<(Vec<u8>, Evaluation)>) {
+        let (results, receiver) = mpsc::unbounded_channel();
+        let evaluator = Evaluator {
+            spectators: spectators.into_inner(),
+            search_options: search_options.into_inner(),
+            registry: registry.into_inner(),
+            results,
+        };
+        (evaluator, receiver)
+    }
+
+    fn request(&self, position: &Position) {
+        if position.outcome != Outcome::Ongoing {
+            return;
+        }
+        let evaluation = self.spectators.evaluation(&position.id, &position.moves);
+        let position = position.clone();
+        let (spectators, search_options, registry, results) = (
+            self.spectators.clone(),
+            self.search_options.clone(),
+            self.registry.clone(),
+            self.results.clone(),
+        );
+        actix_web::rt::spawn(async move {
+            let moves = position.moves.clone();
+            let cancel = evaluation.cancel.clone();
+            // ein Fehler wird nicht gespeichert, der nächste Zuschauer derselben Stellung versucht es erneut
+            let evaluated = evaluation
+                .result
+                .get_or_try_init(|| async move {
+                    let difficulty = Difficulty::from_level(EVALUATION_LEVEL).map_err(|_| ())?;
+                    // beim Herunterfahren wird auch die Bewertung abgebrochen
+                    let _guard = CancelGuard::tracked(cancel.clone(), registry);
+                    let search_options = SearchOptions {
+                        cancel: cancel.clone(),
+                        game_id: Some(position.id.clone()),
+                        ..search_options.as_ref().clone()
+                    };
+                    spectators
+                        .pool()
+                        .run_cancellable(
+                            move || evaluate(&position, &difficulty, &search_options),
+                            || cancel.cancel(),
+                        )
+                        .await
+                        .ok()
+                        .flatten()
+                        .ok_or(())
+                })
+                .await;
+            if let Ok(evaluation) = evaluated {
+                let _ = results.send((moves, evaluation.clone()));
+            }
+        });
+    }
+}
+
+/*
+   Zuschauer-Socket: sendet den Stand nach jeder Änderung als {"type": "position", ...} und, sobald die Engine
+   die Stellung bewertet hat, deren Bewertung als {"type": "evaluation", ...}. Besten Zug und Hauptvariante
+   erhalten Zuschauer erst nach dem Spielende, dann alle Bewertungen der gespielten Stellungen erneut
+   vollständig. Zuschauer können nicht eingreifen, ihre Nachrichten werden ignoriert
+*/
+async fn run_watch(
+    mut feed: Feed,
+    mut position: Position,
+    evaluator: Evaluator,
+    mut results: mpsc::UnboundedReceiver<(Vec<u8>, Evaluation)>,
+    mut session: Session,
+    mut messages: MessageStream,
+) {
+    let _guard = evaluator.spectators.watch(&position.id);
+    let mut heartbeat = actix_web::rt::time::interval(HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+    let mut close_reason = None;
+    // vollständige Bewertungen mit den Zügen ihrer Stellung, bis zum Spielende zurückgehalten
+    let mut withheld: Vec<(Vec<u8>, Evaluation)> = Vec::new();
+
+    evaluator.request(&position);
+    if send(&mut session, &SpectatorEvent::Position(position.clone())).await {
+        loop {
+            tokio::select! {
+                message = messages.recv() => {
+                    last_seen = Instant::now();
+                    match message {
+                        Some(Ok(Message::Ping(bytes))) => {
+                            if session.pong(&bytes).await.is_err() {
+                                break;
+                            }
+                        }
+                        Some(Ok(Message::Close(reason))) => {
+                            close_reason = reason;
+                            break;
+                        }
+                        Some(Ok(_)) => {}
+                        Some(Err(_)) | None => break,
+                    }
+                }
+                next = feed.next() => {
+                    let Some(next) = next else {
+                        close_reason = Some(CloseReason {
+                            code: CloseCode::Away,
+                            description: Some("game removed".to_string()),
+                        });
+                        break;
+                    };
+                    // ein Zug zwischen zwei Menschen meldet Zug und Spielende einzeln, der Stand ist derselbe
+                    if next == position {
+                        continue;
+                    }
+                    position = next;
+                    evaluator.request(&position);
+                    if !send(&mut session, &SpectatorEvent::Position(position.clone())).await {
+                        break;
+                    }
+                    if position.outcome != Outcome::Ongoing
+                        && !reveal(&mut session, &position, &mut withheld).await
+                    {
+                        break;
+                    }
+                }
+                Some((moves, evaluation)) = results.recv() => {
+                    // die Bewertung einer inzwischen veralteten Stellung wird verworfen
+                    if moves != position.moves {
+                        continue;
+                    }
+                    let hidden = evaluation.without_hint();
+                    withheld.push((moves, evaluation));
+                    if !send(&mut session, &SpectatorEvent::Evaluation(hidden)).await {
+                        break;
+                    }
+                }
+                _ = heartbeat.tick() => {
+                    if last_seen.elapsed() > CLIENT_TIMEOUT {
+                        break;
+                    }
+                    if session.ping(b"").await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    let _ = session.close(close_reason).await;
+}
+
+/*
+   Sendet nach dem Spielende die zurückgehaltenen Bewertungen vollständig, nach einer Rücknahme nicht mehr
+   gespielte Stellungen werden verworfen. false, wenn der Client die Verbindung bereits geschlossen hat
+*/
+async fn reveal(
+    session: &mut Session,
+    position: &Position,
+    withheld: &mut Vec<(Vec<u8>, Evaluation)>,
+) -> bool {
+    for (moves, evaluation) in withheld.drain(..) {
+        if position.moves.starts_with(&moves)
+            && !send(session, &SpectatorEvent::Evaluation(evaluation)).await
+        {
+            return false;
+        }
+    }
+    true
+}
+
+// nimmt die WebSocket-Verbindung an und startet den Zuschauer-Socket
+fn start(
+    feed: Feed,
+    position: Position,
+    (evaluator, results): (Evaluator, mpsc::UnboundedReceiver<(Vec<u8>, Evaluation)>),
+    request: &HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (response, session, messages) = actix_ws::handle(request, body)?;
+    actix_web::rt::spawn(run_watch(
+        feed, position, evaluator, results, session, messages,
+    ));
+    Ok(response)
+}
+
+// Zuschauer eines Spiels gegen die Engine
+#[get("/games/{id}/watch")]
+async fn watch_game(
+    store: web::Data<SessionStore>,
+    spectators: web::Data<Spectators>,
+    search_options: web::Data<SearchOptions>,
+    registry: web::Data<SearchRegistry>,
+    id: web::Path<String>,
+    request: HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    let id = id.into_inner();
+    let (game, updates) = match store.watch(&id) {
+        Ok(watched) => watched,
+        Err(error) => return Ok(HttpResponse::NotFound().json(ErrorResponse::new(error))),
+    };
+    let feed = Feed::Game(store.into_inner(), id, updates);
+    start(
+        feed,
+        Position::from_game(&game),
+        Evaluator::new(spectators, search_options, registry),
+        &request,
+        body,
+    )
+}
+
+// Zuschauer eines Spiels zwischen zwei Menschen
+#[get("/matches/{id}/watch")]
+async fn watch_match(
+    store: web::Data<MatchStore>,
+    spectators: web::Data<Spectators>,
+    search_options: web::Data<SearchOptions>,
+    registry: web::Data<SearchRegistry>,
+    id: web::Path<String>,
+    request: HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    let id = id.into_inner();
+    let (game, events) = match store.watch(&id) {
+        Ok(watched) => watched,
+        Err(error) => return Ok(HttpResponse::NotFound().json(ErrorResponse::new(error))),
+    };
+    let feed = Feed::Match(store.into_inner(), id, events);
+    start(
+        feed,
+        Position::from_match(&game),
+        Evaluator::new(spectators, search_options, registry),
+        &request,
+        body,
+    )
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(watch_game).service(watch_match);
+}